@@ -1,19 +1,64 @@
 use serde::Deserialize;
-use serde_json::json;
+use serde_json::{json, Value};
 use std::fs;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
-use chrono::Utc;
+use crate::core::display::{self, Thousands};
+use crate::core::writer;
 
 pub const DEFAULT_MEMORY: u64 = 100;
+pub const DEFAULT_CONTENT_MAX: usize = 8192;
+pub const DEFAULT_DEDUPE_WINDOW: u64 = 60;
+pub const DEFAULT_DEDUPE_SIZE: usize = 32;
 pub const COLLECTION_CORE: &str = "ai.syui.gpt.core";
 pub const COLLECTION_MEMORY: &str = "ai.syui.gpt.memory";
+pub const ENV_DATA_DIR: &str = "AIGPT_DATA_DIR";
 
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+static WARNED: AtomicBool = AtomicBool::new(false);
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Override the data dir for this process (--data-dir)
+pub fn set_data_dir(dir: PathBuf) {
+    let _ = DATA_DIR.set(dir);
+}
+
+/// Refuse record writes and skip state files for this process (--read-only)
+pub fn set_read_only(enabled: bool) {
+    READ_ONLY.store(enabled, Ordering::Relaxed);
+}
+
+pub fn read_only() -> bool {
+    READ_ONLY.load(Ordering::Relaxed)
+}
+
+#[non_exhaustive]
 pub struct Config {
     pub path: Option<String>,
     pub did: Option<String>,
     pub handle: Option<String>,
     pub memory: u64,
+    pub content_max: usize,
+    pub oversize: Oversize,
+    pub safe_mode: bool,
+    /// Seconds a repeated MCP save_memory is answered without writing (0 = off)
+    pub dedupe_window: u64,
+    pub dedupe_size: usize,
+    pub thousands: Thousands,
+    /// Append each CLI invocation to usage.jsonl in the data dir
+    pub usage_log: bool,
+}
+
+/// What to do with content longer than `content_max` bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Oversize {
+    #[default]
+    Reject,
+    Truncate,
+    Chunk,
 }
 
 impl Config {
@@ -41,6 +86,13 @@ struct BotConfig {
     handle: Option<String>,
     path: Option<String>,
     memory: Option<u64>,
+    content_max: Option<usize>,
+    oversize: Option<Oversize>,
+    safe_mode: Option<bool>,
+    dedupe_window: Option<u64>,
+    dedupe_size: Option<usize>,
+    thousands: Option<Thousands>,
+    usage_log: Option<bool>,
 }
 
 pub fn config_file() -> PathBuf {
@@ -50,11 +102,16 @@ pub fn config_file() -> PathBuf {
         .join("config.json")
 }
 
+/// Optional override for the MCP initialize instructions
+pub fn instructions_file() -> PathBuf {
+    config_file().with_file_name("instructions.md")
+}
+
 fn expand_path(p: &str) -> PathBuf {
-    if p.starts_with("~/") {
+    if let Some(rest) = p.strip_prefix("~/") {
         dirs::home_dir()
             .unwrap_or_else(|| PathBuf::from("."))
-            .join(&p[2..])
+            .join(rest)
     } else {
         PathBuf::from(p)
     }
@@ -63,13 +120,30 @@ fn expand_path(p: &str) -> PathBuf {
 pub fn load() -> Config {
     let cfg_path = config_file();
     if let Ok(content) = fs::read_to_string(&cfg_path) {
-        if let Ok(file) = serde_json::from_str::<ConfigFile>(&content) {
+        let parsed = serde_json::from_str::<ConfigFile>(&content);
+        if let Err(e) = &parsed {
+            if !WARNED.swap(true, Ordering::Relaxed) {
+                eprintln!(
+                    "warning: ignoring {}: {} (run: aigpt config validate)",
+                    cfg_path.display(),
+                    e
+                );
+            }
+        }
+        if let Ok(file) = parsed {
             if let Some(bot) = file.bot {
                 return Config {
                     path: bot.path,
                     did: bot.did,
                     handle: bot.handle,
                     memory: bot.memory.unwrap_or(DEFAULT_MEMORY),
+                    content_max: bot.content_max.unwrap_or(DEFAULT_CONTENT_MAX),
+                    oversize: bot.oversize.unwrap_or_default(),
+                    safe_mode: bot.safe_mode.unwrap_or(false),
+                    dedupe_window: bot.dedupe_window.unwrap_or(DEFAULT_DEDUPE_WINDOW),
+                    dedupe_size: bot.dedupe_size.unwrap_or(DEFAULT_DEDUPE_SIZE),
+                    thousands: bot.thousands.unwrap_or_default(),
+                    usage_log: bot.usage_log.unwrap_or(false),
                 };
             }
         }
@@ -80,10 +154,167 @@ pub fn load() -> Config {
         did: None,
         handle: None,
         memory: DEFAULT_MEMORY,
+        content_max: DEFAULT_CONTENT_MAX,
+        oversize: Oversize::default(),
+        safe_mode: false,
+        dedupe_window: DEFAULT_DEDUPE_WINDOW,
+        dedupe_size: DEFAULT_DEDUPE_SIZE,
+        thousands: Thousands::default(),
+        usage_log: false,
     }
 }
 
+/// A problem found by `validate`, with the 1-based line when known
+#[derive(Debug, PartialEq)]
+pub struct Problem {
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+const BOT_KEYS: [&str; 11] = [
+    "did",
+    "handle",
+    "path",
+    "memory",
+    "content_max",
+    "oversize",
+    "safe_mode",
+    "dedupe_window",
+    "dedupe_size",
+    "thousands",
+    "usage_log",
+];
+
+/// Check config.json content: syntax, bot key spelling, value types and
+/// ranges. Keys far from any known name are left alone, since the file is
+/// shared with the site config.
+pub fn validate(content: &str) -> Vec<Problem> {
+    let doc: Value = match serde_json::from_str(content) {
+        Ok(doc) => doc,
+        Err(e) => {
+            return vec![Problem { line: Some(e.line()), message: e.to_string() }];
+        }
+    };
+    let Some(bot) = doc.get("bot") else {
+        return Vec::new();
+    };
+    let Some(bot) = bot.as_object() else {
+        return vec![problem(content, "bot", "bot must be an object")];
+    };
+
+    let mut problems = Vec::new();
+    for key in bot.keys() {
+        if BOT_KEYS.contains(&key.as_str()) {
+            continue;
+        }
+        if let Some(near) = BOT_KEYS.iter().find(|k| edit_distance(key, k) <= 2) {
+            problems.push(problem(
+                content,
+                key,
+                &format!("unknown key bot.{} (did you mean '{}'?)", key, near),
+            ));
+        }
+    }
+
+    for key in ["did", "handle", "path"] {
+        match bot.get(key) {
+            None | Some(Value::Null) | Some(Value::String(_)) => {}
+            Some(v) => {
+                let msg = format!("bot.{} must be a string, got {}", key, v);
+                problems.push(problem(content, key, &msg));
+            }
+        }
+    }
+    if let Some(did) = bot.get("did").and_then(|v| v.as_str()) {
+        if !did.starts_with("did:") {
+            let msg = format!("bot.did '{}' does not start with 'did:'", did);
+            problems.push(problem(content, "did", &msg));
+        }
+    }
+    match bot.get("memory") {
+        None | Some(Value::Null) => {}
+        Some(v) if v.as_u64().is_some_and(|n| n > 0) => {}
+        Some(v) => {
+            let msg = format!("bot.memory must be a positive integer, got {}", v);
+            problems.push(problem(content, "memory", &msg));
+        }
+    }
+    match bot.get("content_max") {
+        None | Some(Value::Null) => {}
+        Some(v) if v.as_u64().is_some() => {}
+        Some(v) => problems.push(problem(
+            content,
+            "content_max",
+            &format!("bot.content_max must be a non-negative integer (0 = unlimited), got {}", v),
+        )),
+    }
+    for key in ["safe_mode", "usage_log"] {
+        match bot.get(key) {
+            None | Some(Value::Null) | Some(Value::Bool(_)) => {}
+            Some(v) => {
+                let msg = format!("bot.{} must be true or false, got {}", key, v);
+                problems.push(problem(content, key, &msg));
+            }
+        }
+    }
+    for key in ["dedupe_window", "dedupe_size"] {
+        match bot.get(key) {
+            None | Some(Value::Null) => {}
+            Some(v) if v.as_u64().is_some() => {}
+            Some(v) => {
+                let msg = format!("bot.{} must be a non-negative integer (0 = off), got {}", key, v);
+                problems.push(problem(content, key, &msg));
+            }
+        }
+    }
+    match bot.get("oversize") {
+        None | Some(Value::Null) => {}
+        Some(v) if serde_json::from_value::<Oversize>(v.clone()).is_ok() => {}
+        Some(v) => problems.push(problem(
+            content,
+            "oversize",
+            &format!("bot.oversize must be one of \"reject\", \"truncate\", \"chunk\", got {}", v),
+        )),
+    }
+    match bot.get("thousands") {
+        None | Some(Value::Null) => {}
+        Some(v) if serde_json::from_value::<Thousands>(v.clone()).is_ok() => {}
+        Some(v) => problems.push(problem(
+            content,
+            "thousands",
+            &format!("bot.thousands must be one of \"none\", \"comma\", \"space\", got {}", v),
+        )),
+    }
+    problems
+}
+
+fn problem(content: &str, key: &str, message: &str) -> Problem {
+    let needle = format!("\"{}\"", key);
+    let after_bot = content.find("\"bot\"").unwrap_or(0);
+    let line = content[after_bot..]
+        .find(&needle)
+        .map(|i| content[..after_bot + i].matches('\n').count() + 1);
+    Problem { line, message: message.to_string() }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur.push((prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
 pub fn init() {
+    if read_only() {
+        return;
+    }
     let cfg_path = config_file();
     if !cfg_path.exists() {
         if let Some(parent) = cfg_path.parent() {
@@ -105,7 +336,7 @@ pub fn init() {
         if let Some(parent) = core_path.parent() {
             let _ = fs::create_dir_all(parent);
         }
-        let now = Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+        let now = display::now();
         let core_record = json!({
             "uri": format!("at://{}/{}/self", cfg.did(), COLLECTION_CORE),
             "value": {
@@ -124,9 +355,20 @@ pub fn init() {
 
     let memory_dir = collection_dir(&cfg, COLLECTION_MEMORY);
     let _ = fs::create_dir_all(&memory_dir);
+
+    for dir in [base_dir(&cfg), collection_dir(&cfg, COLLECTION_CORE), memory_dir] {
+        writer::remove_stale_temps(&dir);
+    }
 }
 
+/// --data-dir > $AIGPT_DATA_DIR > bot.path > $cfg
 pub fn base_dir(cfg: &Config) -> PathBuf {
+    if let Some(dir) = DATA_DIR.get() {
+        return dir.clone();
+    }
+    if let Some(dir) = std::env::var_os(ENV_DATA_DIR).filter(|v| !v.is_empty()) {
+        return expand_path(&dir.to_string_lossy());
+    }
     match &cfg.path {
         Some(p) => expand_path(p),
         None => dirs::config_dir()
@@ -0,0 +1,100 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// Stored timestamps (createdAt, server.json): UTC, second precision
+pub const TIMESTAMP: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+pub fn timestamp(t: DateTime<Utc>) -> String {
+    t.format(TIMESTAMP).to_string()
+}
+
+pub fn now() -> String {
+    timestamp(Utc::now())
+}
+
+/// Digit grouping for counts printed by the CLI (bot.thousands)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Thousands {
+    #[default]
+    None,
+    Comma,
+    Space,
+}
+
+/// Format `n` with the separator between groups of three digits,
+/// independent of the system locale
+pub fn number(n: u64, sep: Thousands) -> String {
+    let digits = n.to_string();
+    let sep = match sep {
+        Thousands::None => return digits,
+        Thousands::Comma => ',',
+        Thousands::Space => ' ',
+    };
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(sep);
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Chars that attach to the one before: ZWJ, variation selectors,
+/// emoji skin tones and tags, combining marks
+fn extends(c: char) -> bool {
+    matches!(c,
+        '\u{200D}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{E0020}'..='\u{E007F}'
+        | '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{3099}'..='\u{309A}')
+}
+
+fn regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+/// Whether cutting at byte `i` keeps emoji sequences, flags and
+/// combining marks whole. An approximation of grapheme boundaries
+/// that needs no Unicode tables.
+pub fn is_cluster_boundary(s: &str, i: usize) -> bool {
+    if i == 0 || i >= s.len() {
+        return true;
+    }
+    if !s.is_char_boundary(i) {
+        return false;
+    }
+    let next = s[i..].chars().next().unwrap();
+    let prev = s[..i].chars().next_back().unwrap();
+    if extends(next) || prev == '\u{200D}' {
+        return false;
+    }
+    if regional_indicator(next) && regional_indicator(prev) {
+        // flags are pairs; odd run before `i` means we are mid-flag
+        let run = s[..i].chars().rev().take_while(|c| regional_indicator(*c)).count();
+        return run % 2 == 0;
+    }
+    true
+}
+
+/// Largest cluster boundary at or below `max` bytes
+pub fn floor_boundary(s: &str, max: usize) -> usize {
+    if max >= s.len() {
+        return s.len();
+    }
+    (0..=max).rev().find(|&i| is_cluster_boundary(s, i)).unwrap_or(0)
+}
+
+/// At most `max` chars of `s` without splitting a cluster, and
+/// whether anything was cut
+pub fn truncate(s: &str, max: usize) -> (&str, bool) {
+    let Some((limit, _)) = s.char_indices().nth(max) else {
+        return (s, false);
+    };
+    (&s[..floor_boundary(s, limit)], true)
+}
@@ -1,3 +1,9 @@
 pub mod config;
+pub mod display;
+pub mod guard;
 pub mod reader;
+pub mod timeline;
+pub mod timing;
+pub mod tokens;
+pub mod usage;
 pub mod writer;
@@ -1,20 +1,124 @@
 use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
 use serde_json::Value;
+use std::borrow::Cow;
 use std::fs;
+use std::path::{Path, PathBuf};
 
 use crate::core::config::{self, COLLECTION_CORE, COLLECTION_MEMORY};
+use crate::core::display;
 
 pub fn read_core() -> Result<Value> {
     let cfg = config::load();
     let path = config::record_path(&cfg, COLLECTION_CORE, "self");
-    let content = fs::read_to_string(&path)
-        .with_context(|| format!("Failed to read {}", path.display()))?;
-    let record: Value = serde_json::from_str(&content)
-        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    read_record(&path)
+}
+
+fn read_record(path: &Path) -> Result<Value> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let (record, _) =
+        parse_record(&bytes).with_context(|| format!("Failed to parse {}", path.display()))?;
     Ok(record)
 }
 
+/// Encoding damage in a record file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Damage {
+    /// Bytes that are not UTF-8; read with U+FFFD in their place
+    InvalidUtf8,
+    /// A \uD800-\uDFFF escape without its pair; read as U+FFFD
+    LoneSurrogate,
+    /// Well-formed, but the text holds U+FFFD from an earlier lossy import
+    ReplacementChar,
+}
+
+impl Damage {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Damage::InvalidUtf8 => "invalid UTF-8",
+            Damage::LoneSurrogate => "lone surrogate escape",
+            Damage::ReplacementChar => "replacement character (U+FFFD)",
+        }
+    }
+}
+
+/// Parse a record file, reading invalid UTF-8 and lone surrogate
+/// escapes lossily rather than failing. The file is not changed;
+/// U+FFFD only appears in the returned value.
+pub fn parse_record(bytes: &[u8]) -> serde_json::Result<(Value, Option<Damage>)> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut damage = matches!(text, Cow::Owned(_)).then_some(Damage::InvalidUtf8);
+    let record: Value = match serde_json::from_str(&text) {
+        Ok(record) => record,
+        Err(e) => {
+            let fixed = replace_lone_surrogates(&text);
+            if fixed == text {
+                return Err(e);
+            }
+            damage = damage.or(Some(Damage::LoneSurrogate));
+            serde_json::from_str(&fixed)?
+        }
+    };
+    if damage.is_none()
+        && record["value"]["content"]["text"].as_str().is_some_and(|t| t.contains('\u{FFFD}'))
+    {
+        damage = Some(Damage::ReplacementChar);
+    }
+    Ok((record, damage))
+}
+
+fn replace_lone_surrogates(json: &str) -> String {
+    let mut out = String::with_capacity(json.len());
+    let mut rest = json;
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        let esc = &rest[i..];
+        let len = match hex_escape(esc) {
+            Some(0xD800..=0xDBFF) if matches!(hex_escape(&esc[6..]), Some(0xDC00..=0xDFFF)) => {
+                out.push_str(&esc[..12]);
+                12
+            }
+            Some(0xD800..=0xDFFF) => {
+                out.push_str("\\ufffd");
+                6
+            }
+            // any other escape, including \\, is copied whole
+            _ => {
+                let len = 1 + esc[1..].chars().next().map_or(0, char::len_utf8);
+                out.push_str(&esc[..len]);
+                len
+            }
+        };
+        rest = &esc[len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn hex_escape(s: &str) -> Option<u32> {
+    let hex = s.strip_prefix("\\u")?.get(..4)?;
+    u32::from_str_radix(hex, 16).ok()
+}
+
+/// Memory record files with encoding damage, in file name order
+pub fn scan_damage() -> Result<Vec<(PathBuf, Value, Damage)>> {
+    let mut found = Vec::new();
+    for path in memory_files()? {
+        let bytes = fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let (record, damage) =
+            parse_record(&bytes).with_context(|| format!("Failed to parse {}", path.display()))?;
+        if let Some(damage) = damage {
+            found.push((path, record, damage));
+        }
+    }
+    Ok(found)
+}
+
 pub fn read_memory_all() -> Result<Vec<Value>> {
+    memory_files()?.iter().map(|path| read_record(path)).collect()
+}
+
+fn memory_files() -> Result<Vec<PathBuf>> {
     let cfg = config::load();
     let dir = config::collection_dir(&cfg, COLLECTION_MEMORY);
     let entries = match fs::read_dir(&dir) {
@@ -22,22 +126,22 @@ pub fn read_memory_all() -> Result<Vec<Value>> {
         Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
         Err(e) => return Err(e).with_context(|| format!("Failed to read {}", dir.display())),
     };
-    let mut files: Vec<_> = entries
+    let mut files: Vec<PathBuf> = entries
         .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
         .collect();
-    files.sort_by_key(|e| e.file_name());
+    files.sort();
+    Ok(files)
+}
 
-    let mut records = Vec::with_capacity(files.len());
-    for entry in &files {
-        let path = entry.path();
-        let content = fs::read_to_string(&path)
-            .with_context(|| format!("Failed to read {}", path.display()))?;
-        let record: Value = serde_json::from_str(&content)
-            .with_context(|| format!("Failed to parse {}", path.display()))?;
-        records.push(record);
-    }
-    Ok(records)
+/// Content text of the memory record at an at:// URI, if its file
+/// exists and parses
+pub fn memory_text(uri: &str) -> Option<String> {
+    let cfg = config::load();
+    let tid = uri.rsplit('/').next()?;
+    let record = read_record(&config::record_path(&cfg, COLLECTION_MEMORY, tid)).ok()?;
+    record["value"]["content"]["text"].as_str().map(str::to_string)
 }
 
 pub fn memory_count() -> usize {
@@ -52,3 +156,69 @@ pub fn memory_count() -> usize {
         })
         .unwrap_or(0)
 }
+
+/// Fingerprint of the current memory records (FNV-1a over the sorted
+/// record keys and their file contents). Changes whenever a record is
+/// added, removed or rewritten.
+pub fn memory_revision() -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for path in memory_files().unwrap_or_default() {
+        let name = path.file_name().unwrap_or_default().as_encoded_bytes().to_vec();
+        let contents = fs::read(&path).unwrap_or_default();
+        for b in name.iter().chain(b"\n").chain(&contents).chain(b"\n") {
+            hash ^= *b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// Record filter shared by read_memory, count_memories and the CLI
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct MemoryFilter {
+    /// createdAt lower bound, in createdAt format (see `parse_since`)
+    pub since: Option<String>,
+    /// Case-insensitive substring of the content text
+    pub query: Option<String>,
+    pub created_by: Option<String>,
+}
+
+impl MemoryFilter {
+    pub fn matches(&self, record: &Value) -> bool {
+        let value = &record["value"];
+        if let Some(since) = &self.since {
+            if value["createdAt"].as_str().is_none_or(|t| t < since.as_str()) {
+                return false;
+            }
+        }
+        if let Some(query) = &self.query {
+            let text = value["content"]["text"].as_str().unwrap_or_default();
+            if !text.to_lowercase().contains(&query.to_lowercase()) {
+                return false;
+            }
+        }
+        if let Some(by) = &self.created_by {
+            if value["createdBy"] != by.as_str() {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Normalize YYYY-MM-DD, RFC 3339, or a relative "30d" to the createdAt
+/// format. Negative or out-of-range day counts give None.
+pub fn parse_since(s: &str) -> Option<String> {
+    if let Some(n) = s.strip_suffix('d') {
+        let days = n.parse::<u32>().ok()?;
+        let t = Utc::now().checked_sub_signed(Duration::try_days(days.into())?)?;
+        return Some(display::timestamp(t));
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(display::timestamp(dt.with_timezone(&Utc)));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .map(|d| display::timestamp(d.and_time(NaiveTime::MIN).and_utc()))
+}
@@ -0,0 +1,88 @@
+//! Chronological grouping of memory records for `aigpt timeline`.
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::core::display;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Group {
+    #[default]
+    Day,
+    /// ISO 8601 week, e.g. 2026-W03
+    Week,
+}
+
+/// One day or week of records, oldest first
+#[derive(Debug, Serialize)]
+pub struct Bucket {
+    pub label: String,
+    pub count: usize,
+    /// Days with no records before this bucket, when longer than the gap
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gap_days: Option<i64>,
+    pub records: Vec<Entry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Entry {
+    pub tid: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    /// First line of the content
+    pub text: String,
+}
+
+/// Bucket records by createdAt. Records without a parsable createdAt
+/// are left out. A run of more than `gap` empty days between two
+/// buckets is reported on the later one; the first bucket never has one.
+pub fn build(records: &[Value], group: Group, gap: u32) -> Vec<Bucket> {
+    let mut dated: Vec<(DateTime<Utc>, &Value)> = records
+        .iter()
+        .filter_map(|r| {
+            let at = r["value"]["createdAt"].as_str()?;
+            let at = DateTime::parse_from_rfc3339(at).ok()?.with_timezone(&Utc);
+            Some((at, r))
+        })
+        .collect();
+    dated.sort_by_key(|(at, _)| *at);
+
+    let mut buckets: Vec<Bucket> = Vec::new();
+    let mut last_day: Option<NaiveDate> = None;
+    for (at, record) in dated {
+        let day = at.date_naive();
+        let label = label(day, group);
+        let empty_days = last_day.map(|last| (day - last).num_days() - 1);
+        last_day = Some(day);
+
+        if buckets.last().is_none_or(|b| b.label != label) {
+            buckets.push(Bucket {
+                label,
+                count: 0,
+                gap_days: empty_days.filter(|days| *days > i64::from(gap)),
+                records: Vec::new(),
+            });
+        }
+        let bucket = buckets.last_mut().unwrap();
+        let uri = record["uri"].as_str().unwrap_or_default();
+        let text = record["value"]["content"]["text"].as_str().unwrap_or_default();
+        bucket.records.push(Entry {
+            tid: uri.rsplit('/').next().unwrap_or_default().to_string(),
+            created_at: display::timestamp(at),
+            text: text.lines().next().unwrap_or_default().to_string(),
+        });
+        bucket.count += 1;
+    }
+    buckets
+}
+
+fn label(day: NaiveDate, group: Group) -> String {
+    match group {
+        Group::Day => day.format("%Y-%m-%d").to_string(),
+        Group::Week => {
+            let week = day.iso_week();
+            format!("{}-W{:02}", week.year(), week.week())
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+/// Named phase durations collected when `--profile` is set.
+/// Disabled timings skip `Instant::now()` entirely.
+pub struct Timings {
+    enabled: bool,
+    spans: Vec<Span>,
+}
+
+struct Span {
+    name: &'static str,
+    total: Duration,
+    count: u32,
+}
+
+impl Timings {
+    pub fn new(enabled: bool) -> Self {
+        Timings { enabled, spans: Vec::new() }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Run `f` and add its duration to the span `name`
+    pub fn time<T>(&mut self, name: &'static str, f: impl FnOnce() -> T) -> T {
+        if !self.enabled {
+            return f();
+        }
+        let start = Instant::now();
+        let out = f();
+        self.record(name, start.elapsed());
+        out
+    }
+
+    /// Repeated names are summed, keeping first-seen order
+    pub fn record(&mut self, name: &'static str, elapsed: Duration) {
+        if !self.enabled {
+            return;
+        }
+        match self.spans.iter_mut().find(|s| s.name == name) {
+            Some(span) => {
+                span.total += elapsed;
+                span.count += 1;
+            }
+            None => self.spans.push(Span { name, total: elapsed, count: 1 }),
+        }
+    }
+
+    pub fn total(&self) -> Duration {
+        self.spans.iter().map(|s| s.total).sum()
+    }
+
+    pub fn report(&self) -> String {
+        let width = self.spans.iter().map(|s| s.name.len()).max().unwrap_or(0).max(5);
+        let mut out = String::from("timings:\n");
+        for s in &self.spans {
+            out.push_str(&format!("  {:<width$}  {:>9.3} ms", s.name, ms(s.total)));
+            if s.count > 1 {
+                out.push_str(&format!("  (x{})", s.count));
+            }
+            out.push('\n');
+        }
+        out.push_str(&format!("  {:<width$}  {:>9.3} ms\n", "total", ms(self.total())));
+        out
+    }
+}
+
+pub fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
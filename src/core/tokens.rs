@@ -0,0 +1,31 @@
+/// Approximate token count without a tokenizer.
+/// ASCII runs average ~4 chars per token in cl100k-style vocabularies,
+/// CJK ideographs and kana are usually one token each, and other
+/// non-ASCII text falls in between.
+pub fn estimate(text: &str) -> usize {
+    let quarters: usize = text
+        .chars()
+        .map(|c| {
+            if c.is_ascii() {
+                1
+            } else if is_cjk(c) {
+                4
+            } else {
+                2
+            }
+        })
+        .sum();
+    quarters.div_ceil(4)
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF     // hiragana, katakana
+        | 0x3400..=0x4DBF   // CJK extension A
+        | 0x4E00..=0x9FFF   // CJK unified ideographs
+        | 0xAC00..=0xD7AF   // hangul syllables
+        | 0xF900..=0xFAFF   // CJK compatibility ideographs
+        | 0xFF66..=0xFF9F   // halfwidth katakana
+        | 0x20000..=0x2FFFF // CJK extensions B+
+    )
+}
@@ -0,0 +1,93 @@
+//! Local usage log (`usage.jsonl` in the data dir), written only when
+//! `bot.usage_log` is true. Nothing here touches the network; the file
+//! is for `aigpt usage report` and whoever reads it by hand.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::core::{config, display, timing};
+
+/// One CLI invocation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub at: String,
+    pub command: String,
+    pub ms: f64,
+    pub ok: bool,
+}
+
+/// Per-command totals, most used first
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Row {
+    pub command: String,
+    pub runs: usize,
+    pub failures: usize,
+    pub avg_ms: f64,
+}
+
+pub fn log_file() -> PathBuf {
+    let cfg = config::load();
+    config::base_dir(&cfg).join("usage.jsonl")
+}
+
+/// Append an entry when the log is enabled (never under --read-only).
+/// Write failures are ignored so logging can never fail the command.
+pub fn record(command: &str, elapsed: Duration, ok: bool) {
+    if config::read_only() || !config::load().usage_log {
+        return;
+    }
+    let entry = Entry {
+        at: display::now(),
+        command: command.to_string(),
+        ms: (timing::ms(elapsed) * 10.0).round() / 10.0,
+        ok,
+    };
+    let Ok(line) = serde_json::to_string(&entry) else {
+        return;
+    };
+    let path = log_file();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+/// Entries in the log; unreadable lines are skipped
+pub fn read() -> Vec<Entry> {
+    fs::read_to_string(log_file())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect()
+}
+
+/// Totals for entries at or after `since` (a createdAt-format timestamp)
+pub fn report(entries: &[Entry], since: Option<&str>) -> Vec<Row> {
+    let mut rows: Vec<Row> = Vec::new();
+    let mut total_ms: Vec<f64> = Vec::new();
+    for e in entries.iter().filter(|e| since.is_none_or(|s| e.at.as_str() >= s)) {
+        let i = match rows.iter().position(|r| r.command == e.command) {
+            Some(i) => i,
+            None => {
+                rows.push(Row { command: e.command.clone(), runs: 0, failures: 0, avg_ms: 0.0 });
+                total_ms.push(0.0);
+                rows.len() - 1
+            }
+        };
+        rows[i].runs += 1;
+        if !e.ok {
+            rows[i].failures += 1;
+        }
+        total_ms[i] += e.ms;
+    }
+    for (row, ms) in rows.iter_mut().zip(total_ms) {
+        row.avg_ms = (ms / row.runs as f64 * 10.0).round() / 10.0;
+    }
+    rows.sort_by(|a, b| b.runs.cmp(&a.runs).then_with(|| a.command.cmp(&b.command)));
+    rows
+}
@@ -1,13 +1,22 @@
-use anyhow::{Context, Result};
-use chrono::Utc;
+use anyhow::{bail, Context, Result};
+use regex::{NoExpand, Regex};
 use serde_json::{json, Value};
 use std::fs;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::core::config::{self, COLLECTION_MEMORY};
+use crate::core::config::{self, Config, Oversize, COLLECTION_MEMORY};
+use crate::core::display;
+use crate::core::reader::{self, Damage, MemoryFilter};
 
 static TID_COUNTER: AtomicU64 = AtomicU64::new(0);
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Temp files older than this are left over from an interrupted write
+const STALE_TMP: Duration = Duration::from_secs(10 * 60);
 
 fn generate_tid() -> String {
     const CHARSET: &[u8] = b"234567abcdefghijklmnopqrstuvwxyz";
@@ -25,9 +34,27 @@ fn generate_tid() -> String {
     tid
 }
 
-fn build_memory_record(did: &str, tid: &str, text: &str) -> Value {
-    let now = Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
-    json!({
+/// Who wrote a record: `createdBy` ("cli", "mcp") and, for MCP, the
+/// clientInfo the client sent on initialize
+#[derive(Debug, Clone)]
+pub struct Provenance {
+    pub created_by: &'static str,
+    pub client: Option<Value>,
+}
+
+impl Provenance {
+    pub fn cli() -> Self {
+        Provenance { created_by: "cli", client: None }
+    }
+
+    pub fn mcp(client: Option<Value>) -> Self {
+        Provenance { created_by: "mcp", client }
+    }
+}
+
+fn build_memory_record(did: &str, tid: &str, text: &str, by: &Provenance) -> Value {
+    let now = display::now();
+    let mut record = json!({
         "uri": format!("at://{}/{}/{}", did, COLLECTION_MEMORY, tid),
         "value": {
             "$type": COLLECTION_MEMORY,
@@ -38,49 +65,390 @@ fn build_memory_record(did: &str, tid: &str, text: &str) -> Value {
             },
             "createdAt": now
         }
-    })
+    });
+    record["value"]["createdBy"] = json!(by.created_by);
+    if let Some(client) = &by.client {
+        record["value"]["client"] = client.clone();
+    }
+    record
+}
+
+/// Outcome of a save after the oversize policy is applied
+#[derive(Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SaveReport {
+    pub records: usize,
+    pub truncated: bool,
+    /// at:// URIs of the records written, in order
+    pub uris: Vec<String>,
 }
 
-/// Save a single memory element as a new TID file
-pub fn save_memory(content: &str) -> Result<()> {
+/// Save a single memory element as a new TID file.
+/// Content over `content_max` bytes is rejected, truncated or split
+/// into `[i/n]` parts according to `oversize`.
+pub fn save_memory(content: &str, by: &Provenance) -> Result<SaveReport> {
+    ensure_writable()?;
+    if content.trim().is_empty() {
+        bail!("content is empty");
+    }
     let cfg = config::load();
-    let tid = generate_tid();
-    let record = build_memory_record(cfg.did(), &tid, content);
+    let (parts, truncated) = apply_limit(&cfg, content)?;
 
     let dir = config::collection_dir(&cfg, COLLECTION_MEMORY);
-    fs::create_dir_all(&dir)
-        .with_context(|| format!("Failed to create {}", dir.display()))?;
-    let path = dir.join(format!("{}.json", tid));
-    let json_str = serde_json::to_string_pretty(&record)?;
-    fs::write(&path, json_str)
-        .with_context(|| format!("Failed to write {}", path.display()))
+    fs::create_dir_all(&dir).map_err(|e| write_error(&dir, e))?;
+    let mut uris = Vec::with_capacity(parts.len());
+    for part in &parts {
+        uris.push(write_memory_record(&cfg, &dir, part, by)?);
+    }
+    Ok(SaveReport { records: parts.len(), truncated, uris })
 }
 
-/// Delete all memory files, then write new ones from the given items
-pub fn compress_memory(items: &[String]) -> Result<()> {
+/// Write new records from the given items, then delete the previous
+/// ones; an interrupted compress leaves both sets rather than neither.
+/// Returns the number of records written.
+pub fn compress_memory(items: &[String], by: &Provenance) -> Result<usize> {
+    ensure_writable()?;
     let cfg = config::load();
     let dir = config::collection_dir(&cfg, COLLECTION_MEMORY);
 
-    // delete all existing memory files
-    if let Ok(entries) = fs::read_dir(&dir) {
-        for entry in entries.flatten() {
-            if entry.path().extension().is_some_and(|ext| ext == "json") {
-                let _ = fs::remove_file(entry.path());
-            }
+    // check every item before anything is written or deleted
+    let mut parts = Vec::with_capacity(items.len());
+    for item in items {
+        parts.extend(apply_limit(&cfg, item)?.0);
+    }
+
+    let previous: Vec<_> = fs::read_dir(&dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    fs::create_dir_all(&dir).map_err(|e| write_error(&dir, e))?;
+
+    for part in &parts {
+        write_memory_record(&cfg, &dir, part, by)?;
+    }
+
+    for path in previous {
+        let _ = fs::remove_file(path);
+    }
+
+    Ok(parts.len())
+}
+
+/// What `plan_replace` looks for: literal text, or a regex whose
+/// replacement text may refer to capture groups as `$1` or `${name}`
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    re: Regex,
+    expand: bool,
+}
+
+impl Pattern {
+    pub fn literal(find: &str) -> Result<Self> {
+        if find.is_empty() {
+            bail!("search text is empty");
         }
+        Ok(Pattern { re: Regex::new(&regex::escape(find))?, expand: false })
     }
 
-    fs::create_dir_all(&dir)
-        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    pub fn regex(find: &str) -> Result<Self> {
+        if find.is_empty() {
+            bail!("search text is empty");
+        }
+        let re = Regex::new(find).with_context(|| format!("Invalid regex '{}'", find))?;
+        Ok(Pattern { re, expand: true })
+    }
 
-    for item in items {
-        let tid = generate_tid();
-        let record = build_memory_record(cfg.did(), &tid, item);
-        let path = dir.join(format!("{}.json", tid));
-        let json_str = serde_json::to_string_pretty(&record)?;
-        fs::write(&path, json_str)
-            .with_context(|| format!("Failed to write {}", path.display()))?;
+    /// Byte range of the first match in `text` and what replaces it
+    pub fn first(&self, text: &str, replace: &str) -> Option<(Range<usize>, String)> {
+        let caps = self.re.captures(text)?;
+        let m = caps.get(0)?;
+        let mut with = String::new();
+        if self.expand {
+            caps.expand(replace, &mut with);
+        } else {
+            with.push_str(replace);
+        }
+        Some((m.range(), with))
+    }
+
+    fn replace_all(&self, text: &str, replace: &str) -> String {
+        if self.expand {
+            self.re.replace_all(text, replace).into_owned()
+        } else {
+            self.re.replace_all(text, NoExpand(replace)).into_owned()
+        }
+    }
+}
+
+/// A pending find/replace on one record
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Replacement {
+    pub uri: String,
+    pub path: PathBuf,
+    pub before: String,
+    pub after: String,
+    /// Occurrences of the pattern in `before`
+    pub matches: usize,
+}
+
+/// Records matching `filter` whose text matches `pattern`, with the text
+/// they would have after replacing every occurrence. Writes nothing.
+pub fn plan_replace(
+    pattern: &Pattern,
+    replace: &str,
+    filter: &MemoryFilter,
+) -> Result<Vec<Replacement>> {
+    let cfg = config::load();
+    let dir = config::collection_dir(&cfg, COLLECTION_MEMORY);
+    let mut plan = Vec::new();
+    for record in reader::read_memory_all()? {
+        let text = record["value"]["content"]["text"].as_str().unwrap_or_default();
+        let matches = pattern.re.find_iter(text).count();
+        if matches == 0 || !filter.matches(&record) {
+            continue;
+        }
+        let uri = record["uri"].as_str().unwrap_or_default().to_string();
+        let tid = uri.rsplit('/').next().unwrap_or_default();
+        let after = pattern.replace_all(text, replace);
+        if cfg.content_max > 0 && after.len() > cfg.content_max {
+            bail!(
+                "{}: result is {} bytes, over the {} byte limit (bot.content_max)",
+                uri,
+                after.len(),
+                cfg.content_max
+            );
+        }
+        plan.push(Replacement {
+            path: dir.join(format!("{}.json", tid)),
+            uri,
+            before: text.to_string(),
+            after,
+            matches,
+        });
     }
+    Ok(plan)
+}
 
+/// Write a plan from `plan_replace`, setting `updatedAt`. Every record
+/// is checked against its planned `before` text first; if any changed
+/// since, nothing is written. All new files are staged before the first
+/// rename. Records whose text would not change are left alone; returns
+/// the number written.
+pub fn apply_replace(plan: &[Replacement]) -> Result<usize> {
+    ensure_writable()?;
+    let plan: Vec<_> = plan.iter().filter(|r| r.after != r.before).collect();
+    let mut records = Vec::with_capacity(plan.len());
+    for r in &plan {
+        let bytes =
+            fs::read(&r.path).with_context(|| format!("Failed to read {}", r.path.display()))?;
+        let (record, damage) = reader::parse_record(&bytes)
+            .with_context(|| format!("Failed to parse {}", r.path.display()))?;
+        if matches!(damage, Some(Damage::InvalidUtf8 | Damage::LoneSurrogate)) {
+            bail!("{} has damaged encoding; run aigpt repair encoding first", r.uri);
+        }
+        if record["value"]["content"]["text"] != r.before.as_str() {
+            bail!("{} changed since the preview; nothing was written", r.uri);
+        }
+        records.push(record);
+    }
+
+    let now = display::now();
+    let mut staged = Vec::with_capacity(plan.len());
+    for (r, mut record) in plan.iter().zip(records) {
+        record["value"]["content"]["text"] = json!(r.after);
+        record["value"]["updatedAt"] = json!(now);
+        let tmp = serde_json::to_string_pretty(&record)
+            .map_err(io::Error::from)
+            .and_then(|json| write_temp(&r.path, &json));
+        match tmp {
+            Ok(tmp) => staged.push((tmp, &r.path)),
+            Err(e) => {
+                for (tmp, _) in &staged {
+                    let _ = fs::remove_file(tmp);
+                }
+                return Err(write_error(&r.path, e));
+            }
+        }
+    }
+    for (tmp, path) in &staged {
+        fs::rename(tmp, path).map_err(|e| write_error(path, e))?;
+    }
+    Ok(staged.len())
+}
+
+/// Rewrite a record read lossily by `reader::parse_record` as valid
+/// UTF-8 JSON, keeping the original bytes in `<file>.bak`
+pub fn repair_record(path: &Path, record: &Value) -> Result<()> {
+    ensure_writable()?;
+    let mut bak = path.as_os_str().to_os_string();
+    bak.push(".bak");
+    fs::copy(path, &bak).map_err(|e| write_error(Path::new(&bak), e))?;
+    write_atomic(path, &serde_json::to_string_pretty(record)?).map_err(|e| write_error(path, e))
+}
+
+/// Fail under --read-only; anything that writes to disk checks this first
+pub fn ensure_writable() -> Result<()> {
+    if config::read_only() {
+        bail!("read-only mode (--read-only): memory records cannot be changed");
+    }
     Ok(())
 }
+
+/// Name the path and, when the filesystem refuses writes, say how to
+/// point aigpt elsewhere
+fn write_error(path: &Path, e: io::Error) -> anyhow::Error {
+    match e.kind() {
+        io::ErrorKind::ReadOnlyFilesystem | io::ErrorKind::PermissionDenied => anyhow::anyhow!(
+            "{} is not writable ({}); pass --data-dir or set AIGPT_DATA_DIR to a writable directory, or use --read-only",
+            path.display(),
+            e
+        ),
+        _ => anyhow::Error::new(e).context(format!("Failed to write {}", path.display())),
+    }
+}
+
+/// Write via a hidden temp file and rename, so readers and a killed
+/// process never leave a half-written file at `path`. An existing file
+/// keeps its permissions, and a symlink is written through to its
+/// target rather than replaced.
+pub fn write_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let (path, permissions) = match fs::canonicalize(path) {
+        Ok(real) => {
+            let permissions = fs::metadata(&real)?.permissions();
+            (real, Some(permissions))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (path.to_path_buf(), None),
+        Err(e) => return Err(e),
+    };
+    let tmp = write_temp(&path, contents)?;
+    let renamed = match permissions {
+        Some(permissions) => fs::set_permissions(&tmp, permissions),
+        None => Ok(()),
+    }
+    .and_then(|()| fs::rename(&tmp, &path));
+    renamed.inspect_err(|_| {
+        let _ = fs::remove_file(&tmp);
+    })
+}
+
+/// Write and sync a hidden temp file next to `path`, returning its path.
+/// The name, `.<file>.<pid>-<n>.tmp`, is unique to this process and call
+/// so concurrent writers never share one.
+fn write_temp(path: &Path, contents: &str) -> io::Result<PathBuf> {
+    let mut tmp_name = std::ffi::OsString::from(".");
+    tmp_name.push(path.file_name().unwrap_or_default());
+    tmp_name.push(format!(
+        ".{}-{}.tmp",
+        std::process::id(),
+        TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let tmp = path.with_file_name(tmp_name);
+    let written = fs::File::create_new(&tmp).and_then(|mut file| {
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()
+    });
+    match written {
+        Ok(()) => Ok(tmp),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
+/// Remove hidden `.*.tmp` files in `dir` older than a few minutes, left
+/// by a write_atomic that was killed before its rename. Errors are
+/// ignored.
+pub fn remove_stale_temps(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if !name.starts_with('.') || !name.ends_with(".tmp") {
+            continue;
+        }
+        let stale = entry
+            .metadata()
+            .and_then(|m| m.modified())
+            .is_ok_and(|t| t.elapsed().is_ok_and(|age| age > STALE_TMP));
+        if stale {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Write one record and return its URI
+fn write_memory_record(
+    cfg: &Config,
+    dir: &Path,
+    text: &str,
+    by: &Provenance,
+) -> Result<String> {
+    let tid = generate_tid();
+    let record = build_memory_record(cfg.did(), &tid, text, by);
+    let path = dir.join(format!("{}.json", tid));
+    let json_str = serde_json::to_string_pretty(&record)?;
+    write_atomic(&path, &json_str).map_err(|e| write_error(&path, e))?;
+    Ok(record["uri"].as_str().unwrap_or_default().to_string())
+}
+
+fn apply_limit(cfg: &Config, text: &str) -> Result<(Vec<String>, bool)> {
+    let max = cfg.content_max;
+    if max == 0 || text.len() <= max {
+        return Ok((vec![text.to_string()], false));
+    }
+    match cfg.oversize {
+        Oversize::Reject => bail!(
+            "content is {} bytes, over the {} byte limit (bot.content_max)",
+            text.len(),
+            max
+        ),
+        Oversize::Truncate => {
+            Ok((vec![text[..display::floor_boundary(text, max)].to_string()], true))
+        }
+        Oversize::Chunk => Ok((chunk(text, max), false)),
+    }
+}
+
+/// Split into parts of at most `max` bytes including the `[i/n] ` label,
+/// breaking after a newline when one falls in the second half of a part
+fn chunk(text: &str, max: usize) -> Vec<String> {
+    // label width grows with the part count; retry until it fits
+    let mut digits = 1;
+    loop {
+        let room = max.saturating_sub(2 * digits + 4).max(4);
+        let mut pieces = Vec::new();
+        let mut rest = text;
+        while !rest.is_empty() {
+            let mut end = display::floor_boundary(rest, room);
+            if end < rest.len() {
+                if let Some(nl) = rest[..end].rfind('\n').filter(|&i| i >= end / 2) {
+                    end = nl + 1;
+                }
+            }
+            if end == 0 {
+                end = rest.chars().next().map_or(rest.len(), char::len_utf8);
+            }
+            pieces.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+        let n = pieces.len();
+        if n.to_string().len() <= digits {
+            return pieces
+                .iter()
+                .enumerate()
+                .map(|(i, p)| format!("[{}/{}] {}", i + 1, n, p))
+                .collect();
+        }
+        digits = n.to_string().len();
+    }
+}
@@ -1,2 +1,55 @@
+//! Memory records for AI clients, stored as atproto-style JSON files,
+//! and an MCP server exposing them.
+//!
+//! The `aigpt` binary is a thin CLI over this crate; everything below
+//! can be embedded. Library code writes:
+//!
+//! - record files, and `<file>.bak` copies from `writer::repair_record`
+//! - config.json and the core `self.json` record, from `config::init`
+//!   when they are missing
+//! - `server.json` (MCP session state) and, when `bot.usage_log` is set,
+//!   `usage.jsonl`
+//! - MCP client config files and their `.bak` copies, from
+//!   `mcp::install`
+//!
+//! With `config::set_read_only(true)` none of these are written. Failures
+//! are reported as `anyhow::Error`; the one thing printed is a stderr
+//! warning for an unparsable config.json.
+//!
+//! Save and read records:
+//!
+//! ```no_run
+//! use aigpt::core::config;
+//! use aigpt::{read_memory_all, save_memory, MemoryFilter, Provenance};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! config::set_data_dir("/tmp/aigpt".into());
+//! save_memory("prefers short answers", &Provenance::cli())?;
+//!
+//! let mut filter = MemoryFilter::default();
+//! filter.query = Some("short".into());
+//! for record in read_memory_all()?.iter().filter(|r| filter.matches(r)) {
+//!     println!("{}", record["value"]["content"]["text"]);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Run the MCP server over stdio, or over any reader and writer:
+//!
+//! ```no_run
+//! use aigpt::MCPServer;
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let server = MCPServer::new().safe_mode(true);
+//! server.serve(std::io::stdin().lock(), std::io::stdout())?;
+//! # Ok(())
+//! # }
+//! ```
+
 pub mod core;
-pub mod mcp;
\ No newline at end of file
+pub mod mcp;
+
+pub use core::reader::{read_core, read_memory_all, MemoryFilter};
+pub use core::writer::{compress_memory, save_memory, Provenance, SaveReport};
+pub use mcp::MCPServer;
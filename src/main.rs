@@ -1,8 +1,15 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use std::process::Command;
+use std::time::{Duration, Instant};
 
-use aigpt::core::{config, reader, writer};
+use aigpt::core::reader::{Damage, MemoryFilter};
+use aigpt::core::timeline::{self, Group};
+use aigpt::core::timing::Timings;
+use aigpt::core::writer::{Pattern, Provenance};
+use aigpt::core::{config, display, guard, reader, tokens, usage, writer};
+use aigpt::mcp::install::{self, Change, Client, Health};
+use aigpt::mcp::server::{DESTRUCTIVE_TOOLS, WRITE_TOOLS};
 use aigpt::mcp::MCPServer;
 
 #[derive(Parser)]
@@ -13,6 +20,18 @@ struct Cli {
     #[arg(short = 'v', long = "version")]
     version: bool,
 
+    /// Print a timing breakdown to stderr
+    #[arg(long, global = true)]
+    profile: bool,
+
+    /// Data directory for records (overrides $AIGPT_DATA_DIR and bot.path)
+    #[arg(long, global = true, value_name = "DIR")]
+    data_dir: Option<std::path::PathBuf>,
+
+    /// Never write: refuse record changes and skip config/state files
+    #[arg(long, global = true)]
+    read_only: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -27,21 +46,227 @@ enum Commands {
     Setup,
 
     /// Start MCP server (JSON-RPC over stdio)
-    Server,
+    Server {
+        /// Disable tools that delete records (also bot.safe_mode)
+        #[arg(long)]
+        safe_mode: bool,
+    },
 
     /// Read core record
+    #[command(visible_alias = "core")]
     ReadCore,
 
     /// Read all memory records
-    ReadMemory,
+    #[command(visible_aliases = ["ls", "list"])]
+    ReadMemory(ReadMemoryArgs),
+
+    /// Add a single memory element
+    #[command(visible_aliases = ["new", "save"])]
+    SaveMemory(SaveMemoryArgs),
+
+    /// Count memory records
+    Count(FilterArgs),
+
+    /// Memory records by day or week, with gaps called out
+    Timeline(TimelineArgs),
+
+    /// Replace text across memory records (preview unless --yes)
+    Replace(ReplaceArgs),
+
+    /// Memory records (same as the flat read-memory/save-memory)
+    Memory {
+        #[command(subcommand)]
+        command: MemoryCommand,
+    },
+
+    /// Inspect the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Register aigpt in an MCP client's config
+    Mcp {
+        #[command(subcommand)]
+        command: McpCommand,
+    },
+
+    /// Find and fix damaged record files
+    Repair {
+        #[command(subcommand)]
+        command: RepairCommand,
+    },
+
+    /// Summarize the local usage log (bot.usage_log)
+    Usage {
+        #[command(subcommand)]
+        command: UsageCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum MemoryCommand {
+    /// Read all memory records
+    #[command(visible_alias = "ls")]
+    List(ReadMemoryArgs),
 
     /// Add a single memory element
-    SaveMemory {
-        /// Content to write
-        content: String,
+    #[command(visible_alias = "new")]
+    Save(SaveMemoryArgs),
+}
+
+#[derive(Args)]
+struct ReadMemoryArgs {
+    /// Only records written via this path (cli, mcp)
+    #[arg(long, value_name = "SOURCE")]
+    created_by: Option<String>,
+
+    /// Only records that look like prompt-injection attempts
+    #[arg(long)]
+    suspicious: bool,
+}
+
+#[derive(Args)]
+struct FilterArgs {
+    /// Only records created at or after this date (YYYY-MM-DD, RFC 3339, or 30d)
+    #[arg(long, value_name = "DATE")]
+    since: Option<String>,
+
+    /// Only records whose content contains this text (case-insensitive)
+    #[arg(long)]
+    query: Option<String>,
+
+    /// Only records written via this path (cli, mcp)
+    #[arg(long, value_name = "SOURCE")]
+    created_by: Option<String>,
+}
+
+impl FilterArgs {
+    fn filter(&self) -> Result<MemoryFilter> {
+        let mut filter = MemoryFilter::default();
+        if let Some(s) = &self.since {
+            filter.since = Some(reader::parse_since(s).with_context(|| {
+                format!("Invalid --since '{}': expected YYYY-MM-DD, RFC 3339, or Nd", s)
+            })?);
+        }
+        filter.query = self.query.clone();
+        filter.created_by = self.created_by.clone();
+        Ok(filter)
+    }
+}
+
+#[derive(Args)]
+struct TimelineArgs {
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    /// Bucket size
+    #[arg(long, value_enum, default_value_t = GroupArg::Day)]
+    group: GroupArg,
+
+    /// Call out runs of more than this many days with no records
+    #[arg(long, value_name = "DAYS", default_value_t = 7)]
+    gap: u32,
+
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+#[derive(Args)]
+struct ReplaceArgs {
+    /// Text to look for (case-sensitive; literal unless --regex)
+    #[arg(long)]
+    find: String,
+
+    /// Text to put in its place; with --regex, $1 or ${name} insert a
+    /// capture group
+    #[arg(long)]
+    replace: String,
+
+    /// Treat --find as a regular expression
+    #[arg(long)]
+    regex: bool,
+
+    /// Only show what would change, even with --yes
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Apply the changes
+    #[arg(long)]
+    yes: bool,
+
+    #[command(flatten)]
+    filter: FilterArgs,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GroupArg {
+    Day,
+    Week,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Args)]
+struct SaveMemoryArgs {
+    /// Content to write
+    content: String,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Check config.json for syntax errors, misspelled keys and bad values
+    Validate,
+}
+
+#[derive(Subcommand)]
+enum McpCommand {
+    /// Add or update the aigpt server entry
+    Install(ClientArgs),
+
+    /// Remove the aigpt server entry
+    Uninstall(ClientArgs),
+
+    /// Check the entry points at this executable
+    Doctor(ClientArgs),
+}
+
+#[derive(Subcommand)]
+enum RepairCommand {
+    /// Records with invalid UTF-8, lone surrogate escapes or U+FFFD;
+    /// the first two are rewritten as valid UTF-8 (original kept as .bak)
+    Encoding {
+        /// Only list what was found
+        #[arg(long)]
+        dry_run: bool,
     },
 }
 
+#[derive(Subcommand)]
+enum UsageCommand {
+    /// Runs, failures and average duration per command
+    Report {
+        /// Only invocations at or after this date (YYYY-MM-DD, RFC 3339, or 30d)
+        #[arg(long, value_name = "DATE")]
+        since: Option<String>,
+    },
+}
+
+#[derive(Args)]
+struct ClientArgs {
+    /// MCP client to configure
+    #[arg(long, default_value = "claude-code", value_parser = Client::NAMES)]
+    client: String,
+
+    /// Config file to edit instead of the client's default
+    #[arg(long, value_name = "FILE")]
+    config: Option<std::path::PathBuf>,
+}
+
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
@@ -50,55 +275,191 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
+    if let Some(dir) = &cli.data_dir {
+        config::set_data_dir(dir.clone());
+    }
+    config::set_read_only(cli.read_only);
+
+    let name = command_name(&cli.command);
+    let start = Instant::now();
+    let result = run(cli);
+    usage::record(name, start.elapsed(), result.is_ok());
+    result
+}
+
+fn run(cli: Cli) -> Result<()> {
     match &cli.command {
         Some(Commands::Version) => {
             println!("{}", env!("CARGO_PKG_VERSION"));
             return Ok(());
         }
         Some(Commands::Setup) => return run_setup(),
+        Some(Commands::Config { command }) => return run_config(command),
+        Some(Commands::Mcp { command }) => return run_mcp(command),
+        Some(Commands::Usage { command }) => return run_usage(command),
         _ => {}
     }
 
-    config::init();
+    let mut t = Timings::new(cli.profile);
+    t.time("init", config::init);
 
     match cli.command {
         None => {
-            print_status();
+            t.time("status", print_status);
         }
 
-        Some(Commands::Server) => {
-            let server = MCPServer::new();
+        Some(Commands::Server { safe_mode }) => {
+            let cfg = config::load();
+            let safe_mode = safe_mode || cfg.safe_mode;
+            if config::read_only() {
+                eprintln!("aigpt: read-only mode, disabled tools: {}", WRITE_TOOLS.join(", "));
+            } else if safe_mode {
+                eprintln!("aigpt: safe mode, disabled tools: {}", DESTRUCTIVE_TOOLS.join(", "));
+            }
+            let server = MCPServer::new()
+                .profile(cli.profile)
+                .safe_mode(safe_mode)
+                .dedupe(Duration::from_secs(cfg.dedupe_window), cfg.dedupe_size);
             server.run()?;
         }
 
         Some(Commands::ReadCore) => {
-            let record = reader::read_core()?;
-            println!("{}", serde_json::to_string_pretty(&record)?);
+            let record = t.time("read", reader::read_core)?;
+            let out = t.time("serialize", || serde_json::to_string_pretty(&record))?;
+            println!("{}", out);
         }
 
-        Some(Commands::ReadMemory) => {
-            let records = reader::read_memory_all()?;
+        Some(Commands::ReadMemory(args))
+        | Some(Commands::Memory { command: MemoryCommand::List(args) }) => {
+            let mut records = t.time("read", reader::read_memory_all)?;
+            let mut filter = MemoryFilter::default();
+            filter.created_by = args.created_by;
+            records.retain(|r| filter.matches(r));
+            if args.suspicious {
+                records.retain(|r| {
+                    let text = r["value"]["content"]["text"].as_str().unwrap_or_default();
+                    match guard::suspicious(text) {
+                        Some(reason) => {
+                            eprintln!("{}: {}", r["uri"].as_str().unwrap_or_default(), reason);
+                            true
+                        }
+                        None => false,
+                    }
+                });
+            }
             if records.is_empty() {
                 println!("No memory records found");
             } else {
                 for record in &records {
-                    println!("{}", serde_json::to_string_pretty(record)?);
+                    let out = t.time("serialize", || serde_json::to_string_pretty(record))?;
+                    println!("{}", out);
+                }
+            }
+        }
+
+        Some(Commands::Count(args)) => {
+            let filter = args.filter()?;
+            let records = t.time("read", reader::read_memory_all)?;
+            println!("{}", records.iter().filter(|r| filter.matches(r)).count());
+        }
+
+        Some(Commands::Timeline(args)) => {
+            let filter = args.filter.filter()?;
+            let mut records = t.time("read", reader::read_memory_all)?;
+            records.retain(|r| filter.matches(r));
+            let group = match args.group {
+                GroupArg::Day => Group::Day,
+                GroupArg::Week => Group::Week,
+            };
+            let buckets = t.time("group", || timeline::build(&records, group, args.gap));
+            if args.format == Format::Json {
+                println!("{}", serde_json::to_string_pretty(&buckets)?);
+            } else if buckets.is_empty() {
+                println!("No memory records found");
+            } else {
+                print!("{}", render_timeline(&buckets, group));
+            }
+        }
+
+        Some(Commands::Replace(args)) => {
+            let filter = args.filter.filter()?;
+            let pattern = if args.regex {
+                Pattern::regex(&args.find)?
+            } else {
+                Pattern::literal(&args.find)?
+            };
+            let plan = t.time("plan", || writer::plan_replace(&pattern, &args.replace, &filter))?;
+            if plan.is_empty() {
+                let verb = if args.regex { "match" } else { "contain" };
+                println!("No memory records {} '{}'", verb, args.find);
+            } else {
+                let total: usize = plan.iter().map(|r| r.matches).sum();
+                println!("{} records, {} occurrences", plan.len(), total);
+                for r in plan.iter().take(10) {
+                    println!("  {}", r.uri);
+                    println!("    {}", replace_snippet(&r.before, &pattern, &args.replace));
+                }
+                if plan.len() > 10 {
+                    println!("  ... and {} more", plan.len() - 10);
+                }
+                if args.yes && !args.dry_run {
+                    let n = t.time("write", || writer::apply_replace(&plan))?;
+                    println!("Updated {} records.", n);
+                } else if !args.dry_run {
+                    println!("Run again with --yes to apply.");
                 }
             }
         }
 
-        Some(Commands::SaveMemory { content }) => {
-            writer::save_memory(&content)?;
-            println!("Saved. ({} records)", reader::memory_count());
+        Some(Commands::Repair { command: RepairCommand::Encoding { dry_run } }) => {
+            let found = t.time("scan", reader::scan_damage)?;
+            let mut repaired = 0;
+            for (path, record, damage) in &found {
+                println!("{}  {}", record["uri"].as_str().unwrap_or_default(), damage.describe());
+                if !dry_run && *damage != Damage::ReplacementChar {
+                    writer::repair_record(path, record)?;
+                    repaired += 1;
+                }
+            }
+            if found.is_empty() {
+                println!("No encoding problems found");
+            } else if dry_run {
+                println!("{} records with encoding problems (dry run)", found.len());
+            } else {
+                println!("{} records with encoding problems, {} rewritten", found.len(), repaired);
+            }
+        }
+
+        Some(Commands::SaveMemory(args))
+        | Some(Commands::Memory { command: MemoryCommand::Save(args) }) => {
+            let content = args.content;
+            let report = t.time("write", || writer::save_memory(&content, &Provenance::cli()))?;
+            let count = t.time("count", reader::memory_count);
+            if report.truncated {
+                println!("Truncated to {} bytes (bot.content_max).", config::load().content_max);
+            }
+            if report.records > 1 {
+                println!("Split into {} parts.", report.records);
+            }
+            println!("Saved. ({} records)", count);
         }
 
-        Some(Commands::Version) | Some(Commands::Setup) => unreachable!(),
+        Some(Commands::Version)
+        | Some(Commands::Setup)
+        | Some(Commands::Config { .. })
+        | Some(Commands::Usage { .. })
+        | Some(Commands::Mcp { .. }) => unreachable!(),
+    }
+
+    if t.enabled() {
+        eprint!("{}", t.report());
     }
 
     Ok(())
 }
 
 fn run_setup() -> Result<()> {
+    writer::ensure_writable()?;
     let cfg_dir = config::config_file()
         .parent()
         .unwrap()
@@ -152,6 +513,137 @@ fn run_setup() -> Result<()> {
     Ok(())
 }
 
+fn run_config(command: &ConfigCommand) -> Result<()> {
+    match command {
+        ConfigCommand::Validate => {
+            let path = config::config_file();
+            let content = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let problems = config::validate(&content);
+            for p in &problems {
+                match p.line {
+                    Some(line) => eprintln!("{}:{}: {}", path.display(), line, p.message),
+                    None => eprintln!("{}: {}", path.display(), p.message),
+                }
+            }
+            if !problems.is_empty() {
+                anyhow::bail!("{} problem(s) in {}", problems.len(), path.display());
+            }
+            println!("ok {}", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn run_mcp(command: &McpCommand) -> Result<()> {
+    let (McpCommand::Install(args) | McpCommand::Uninstall(args) | McpCommand::Doctor(args)) =
+        command;
+    let client = Client::from_name(&args.client).unwrap();
+    let path = match &args.config {
+        Some(path) => path.clone(),
+        None => client
+            .config_path()
+            .ok_or_else(|| anyhow::anyhow!("Cannot find config for {}", args.client))?,
+    };
+    let exe = std::env::current_exe()?;
+
+    match command {
+        McpCommand::Install(_) => {
+            writer::ensure_writable()?;
+            match install::install(&path, client.entry(&exe))? {
+                Change::Added => println!("ok added aigpt to {}", path.display()),
+                Change::Updated { previous } => {
+                    println!("ok updated aigpt in {}", path.display());
+                    println!("  was: {}", previous);
+                }
+                Change::Unchanged => {
+                    println!("ok aigpt already up to date in {}", path.display());
+                    return Ok(());
+                }
+            }
+            println!("  command: {} server", exe.display());
+            let bak = install::backup_path(&path);
+            if bak.exists() {
+                println!("  backup: {}", bak.display());
+            }
+        }
+        McpCommand::Uninstall(_) => {
+            writer::ensure_writable()?;
+            if install::uninstall(&path)? {
+                println!("ok removed aigpt from {}", path.display());
+            } else {
+                println!("skip aigpt not found in {}", path.display());
+            }
+        }
+        McpCommand::Doctor(_) => match install::doctor(&path, &exe)? {
+            Health::Ok => println!("ok {} -> {}", path.display(), exe.display()),
+            Health::Missing => anyhow::bail!(
+                "aigpt is not registered in {} (run: aigpt mcp install --client {})",
+                path.display(),
+                args.client
+            ),
+            Health::Mismatch { command } => anyhow::bail!(
+                "{} runs {}, not {} (run: aigpt mcp install --client {})",
+                path.display(),
+                command,
+                exe.display(),
+                args.client
+            ),
+        },
+    }
+    Ok(())
+}
+
+fn run_usage(command: &UsageCommand) -> Result<()> {
+    let UsageCommand::Report { since } = command;
+    let since = match since {
+        Some(s) => Some(reader::parse_since(s).with_context(|| {
+            format!("Invalid --since '{}': expected YYYY-MM-DD, RFC 3339, or Nd", s)
+        })?),
+        None => None,
+    };
+    let rows = usage::report(&usage::read(), since.as_deref());
+    if rows.is_empty() {
+        if config::load().usage_log {
+            println!("No usage recorded in {}", usage::log_file().display());
+        } else {
+            println!("Usage log is off; set bot.usage_log to true to record invocations locally.");
+        }
+        return Ok(());
+    }
+    let width = rows.iter().map(|r| r.command.len()).max().unwrap_or(0).max(7);
+    println!("{:<width$}  {:>5}  {:>8}  {:>9}", "command", "runs", "failed", "avg ms");
+    for r in &rows {
+        let failed = format!("{:.0}%", r.failures as f64 * 100.0 / r.runs as f64);
+        println!("{:<width$}  {:>5}  {:>8}  {:>9.1}", r.command, r.runs, failed, r.avg_ms);
+    }
+    Ok(())
+}
+
+/// Canonical name for the usage log, independent of the alias typed
+fn command_name(command: &Option<Commands>) -> &'static str {
+    match command {
+        None => "status",
+        Some(Commands::Version) => "v",
+        Some(Commands::Setup) => "setup",
+        Some(Commands::Server { .. }) => "server",
+        Some(Commands::ReadCore) => "read-core",
+        Some(Commands::ReadMemory(_)) => "read-memory",
+        Some(Commands::SaveMemory(_)) => "save-memory",
+        Some(Commands::Count(_)) => "count",
+        Some(Commands::Timeline(_)) => "timeline",
+        Some(Commands::Replace(_)) => "replace",
+        Some(Commands::Repair { command: RepairCommand::Encoding { .. } }) => "repair encoding",
+        Some(Commands::Memory { command: MemoryCommand::List(_) }) => "memory list",
+        Some(Commands::Memory { command: MemoryCommand::Save(_) }) => "memory save",
+        Some(Commands::Config { command: ConfigCommand::Validate }) => "config validate",
+        Some(Commands::Mcp { command: McpCommand::Install(_) }) => "mcp install",
+        Some(Commands::Mcp { command: McpCommand::Uninstall(_) }) => "mcp uninstall",
+        Some(Commands::Mcp { command: McpCommand::Doctor(_) }) => "mcp doctor",
+        Some(Commands::Usage { command: UsageCommand::Report { .. } }) => "usage report",
+    }
+}
+
 fn which_command(cmd: &str) -> Option<std::path::PathBuf> {
     Command::new("which")
         .arg(cmd)
@@ -165,6 +657,45 @@ fn is_command_available(cmd: &str) -> bool {
     which_command(cmd).is_some()
 }
 
+fn render_timeline(buckets: &[timeline::Bucket], group: Group) -> String {
+    let mut out = String::new();
+    for bucket in buckets {
+        if let Some(days) = bucket.gap_days {
+            out.push_str(&format!("  — {} days with no memories —\n", days));
+        }
+        out.push_str(&format!("{} ({})\n", bucket.label, bucket.count));
+        for entry in &bucket.records {
+            // createdAt is display::TIMESTAMP: YYYY-MM-DDTHH:MM:SSZ
+            let when = match group {
+                Group::Day => &entry.created_at[11..16],
+                Group::Week => &entry.created_at[5..16],
+            };
+            let (text, cut) = display::truncate(&entry.text, 72);
+            let more = if cut { "…" } else { "" };
+            out.push_str(&format!("  {}  {}  {}{}\n", when.replace('T', " "), entry.tid, text, more));
+        }
+    }
+    out
+}
+
+/// First occurrence with some context, marked `{-old-}{+new+}`
+fn replace_snippet(text: &str, pattern: &Pattern, replace: &str) -> String {
+    const CONTEXT: usize = 30;
+    let (range, with) = pattern.first(text, replace).unwrap_or_default();
+    let (at, end) = (range.start, range.end);
+    let find = &text[at..end];
+    let mut start = text[..at].char_indices().rev().nth(CONTEXT - 1).map_or(0, |(i, _)| i);
+    while start < at && !display::is_cluster_boundary(text, start) {
+        start += text[start..].chars().next().map_or(1, char::len_utf8);
+    }
+    let before = &text[start..at];
+    let (after, cut) = display::truncate(&text[end..], CONTEXT);
+    let lead = if start > 0 { "…" } else { "" };
+    let tail = if cut { "…" } else { "" };
+    format!("{}{}{{-{}-}}{{+{}+}}{}{}", lead, before, find, with, after, tail)
+        .replace('\n', " ")
+}
+
 fn print_status() {
     let cfg = config::load();
     let base = config::base_dir(&cfg);
@@ -174,11 +705,24 @@ fn print_status() {
     println!("config: {}", config::config_file().display());
     println!("did:    {}", cfg.did());
     println!("handle: {}", cfg.handle());
-    println!("memory: {}", cfg.memory);
+    let n = |n: u64| display::number(n, cfg.thousands);
+    println!("memory: {}", n(cfg.memory));
     println!();
     println!("path: {}/", base.display());
     println!("  {}/{}/self.json", cfg.identity(), config::COLLECTION_CORE);
     println!("  {}/{}/*.json", cfg.identity(), config::COLLECTION_MEMORY);
     println!();
-    println!("records: {}/{}", count, cfg.memory);
+    println!("records: {}/{}", n(count as u64), n(cfg.memory));
+    println!("tokens:  ~{}", n(memory_tokens() as u64));
+}
+
+/// Estimated tokens of the core and memory text sent on initialize
+fn memory_tokens() -> usize {
+    let core = reader::read_core().ok();
+    let records = reader::read_memory_all().unwrap_or_default();
+    core.iter()
+        .chain(records.iter())
+        .filter_map(|r| r["value"]["content"]["text"].as_str())
+        .map(tokens::estimate)
+        .sum()
 }
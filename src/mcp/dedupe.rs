@@ -0,0 +1,93 @@
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use crate::core::config::{DEFAULT_DEDUPE_SIZE, DEFAULT_DEDUPE_WINDOW};
+use crate::core::reader;
+
+/// Recent write calls, newest last. A repeat of the same call inside
+/// `window` is answered from here instead of writing again, as long as
+/// the records that call wrote still exist with the same text.
+pub struct Dedupe {
+    window: Duration,
+    size: usize,
+    recent: VecDeque<Entry>,
+}
+
+struct Entry {
+    key: u64,
+    at: Instant,
+    /// URI and text of each record the call wrote
+    written: Vec<(String, String)>,
+    result: Value,
+}
+
+impl Dedupe {
+    /// A zero window or size disables deduplication
+    pub fn new(window: Duration, size: usize) -> Self {
+        Dedupe { window, size, recent: VecDeque::new() }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.window.is_zero() && self.size > 0
+    }
+
+    /// Key for a tool call; content is compared with surrounding
+    /// whitespace trimmed
+    pub fn key(tool: &str, content: &str) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        tool.hash(&mut hasher);
+        content.trim().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Prior result for `key` if it was recorded within the window and
+    /// every record it wrote is still there unchanged
+    pub fn lookup(&mut self, key: u64, now: Instant) -> Option<Value> {
+        let window = self.window;
+        self.recent.retain(|e| now.saturating_duration_since(e.at) < window);
+        let i = self.recent.iter().rposition(|e| e.key == key)?;
+        let intact = self.recent[i]
+            .written
+            .iter()
+            .all(|(uri, text)| reader::memory_text(uri).as_deref() == Some(text.as_str()));
+        if !intact {
+            self.recent.remove(i);
+            return None;
+        }
+        Some(self.recent[i].result.clone())
+    }
+
+    /// Remember `result` for `key`; `uris` are the records the call wrote.
+    /// Nothing is kept if one of them cannot be read back.
+    pub fn record(&mut self, key: u64, result: Value, now: Instant, uris: &[String]) {
+        if !self.enabled() {
+            return;
+        }
+        let Some(written) = uris
+            .iter()
+            .map(|uri| reader::memory_text(uri).map(|text| (uri.clone(), text)))
+            .collect()
+        else {
+            return;
+        };
+        self.recent.retain(|e| e.key != key);
+        self.recent.push_back(Entry { key, at: now, written, result });
+        while self.recent.len() > self.size {
+            self.recent.pop_front();
+        }
+    }
+
+    /// Forget every entry, e.g. after the records were replaced
+    pub fn clear(&mut self) {
+        self.recent.clear();
+    }
+}
+
+impl Default for Dedupe {
+    fn default() -> Self {
+        Dedupe::new(Duration::from_secs(DEFAULT_DEDUPE_WINDOW), DEFAULT_DEDUPE_SIZE)
+    }
+}
@@ -0,0 +1,164 @@
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::core::writer;
+
+pub const SERVER_NAME: &str = "aigpt";
+
+/// MCP clients whose config files `aigpt mcp install` can edit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Client {
+    ClaudeCode,
+    ClaudeDesktop,
+    Cursor,
+}
+
+impl Client {
+    pub const NAMES: [&'static str; 3] = ["claude-code", "claude-desktop", "cursor"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "claude-code" => Some(Client::ClaudeCode),
+            "claude-desktop" => Some(Client::ClaudeDesktop),
+            "cursor" => Some(Client::Cursor),
+            _ => None,
+        }
+    }
+
+    /// User-scope config file for this client on the current platform
+    pub fn config_path(&self) -> Option<PathBuf> {
+        match self {
+            Client::ClaudeCode => dirs::home_dir().map(|h| h.join(".claude.json")),
+            Client::ClaudeDesktop => {
+                dirs::config_dir().map(|c| c.join("Claude").join("claude_desktop_config.json"))
+            }
+            Client::Cursor => dirs::home_dir().map(|h| h.join(".cursor").join("mcp.json")),
+        }
+    }
+
+    /// mcpServers entry launching `exe server`
+    pub fn entry(&self, exe: &Path) -> Value {
+        let mut entry = json!({
+            "command": exe.to_string_lossy(),
+            "args": ["server"]
+        });
+        if *self == Client::ClaudeCode {
+            entry["type"] = json!("stdio");
+        }
+        entry
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Added,
+    Updated { previous: Value },
+    Unchanged,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Health {
+    Ok,
+    Missing,
+    /// Entry exists but launches a different command
+    Mismatch { command: String },
+}
+
+/// Insert or update the aigpt entry. An existing file is copied to
+/// `<file>.bak` before it is replaced.
+pub fn install(path: &Path, entry: Value) -> Result<Change> {
+    let mut doc = read_config(path)?;
+    let servers = servers_mut(&mut doc, path)?;
+    let change = match servers.get(SERVER_NAME) {
+        None => Change::Added,
+        Some(prev) if *prev == entry => return Ok(Change::Unchanged),
+        Some(prev) => Change::Updated { previous: prev.clone() },
+    };
+    servers.insert(SERVER_NAME.to_string(), entry);
+    write_config(path, &doc)?;
+    Ok(change)
+}
+
+/// Remove the aigpt entry; returns false when there was none
+pub fn uninstall(path: &Path) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let mut doc = read_config(path)?;
+    let removed = servers_mut(&mut doc, path)?.remove(SERVER_NAME).is_some();
+    if removed {
+        write_config(path, &doc)?;
+    }
+    Ok(removed)
+}
+
+/// Check that the entry launches `exe`
+pub fn doctor(path: &Path, exe: &Path) -> Result<Health> {
+    if !path.exists() {
+        return Ok(Health::Missing);
+    }
+    let doc = read_config(path)?;
+    let Some(entry) = doc.get("mcpServers").and_then(|s| s.get(SERVER_NAME)) else {
+        return Ok(Health::Missing);
+    };
+    let command = entry["command"].as_str().unwrap_or_default();
+    if Path::new(command) == exe {
+        Ok(Health::Ok)
+    } else {
+        Ok(Health::Mismatch { command: command.to_string() })
+    }
+}
+
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".bak");
+    path.with_file_name(name)
+}
+
+fn read_config(path: &Path) -> Result<Value> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(json!({})),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    if content.trim().is_empty() {
+        return Ok(json!({}));
+    }
+    match serde_json::from_str::<Value>(&content) {
+        Ok(doc) if doc.is_object() => Ok(doc),
+        Ok(_) => bail!("{} is not a JSON object; edit it by hand", path.display()),
+        Err(e) => bail!(
+            "{} is not plain JSON ({}); comments and trailing commas are not supported, edit it by hand",
+            path.display(),
+            e
+        ),
+    }
+}
+
+fn servers_mut<'a>(doc: &'a mut Value, path: &Path) -> Result<&'a mut Map<String, Value>> {
+    let servers = doc
+        .as_object_mut()
+        .unwrap()
+        .entry("mcpServers")
+        .or_insert_with(|| json!({}));
+    match servers.as_object_mut() {
+        Some(map) => Ok(map),
+        None => bail!("mcpServers in {} is not an object", path.display()),
+    }
+}
+
+fn write_config(path: &Path, doc: &Value) -> Result<()> {
+    writer::ensure_writable()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    if path.exists() {
+        let bak = backup_path(path);
+        fs::copy(path, &bak).with_context(|| format!("Failed to write {}", bak.display()))?;
+    }
+    writer::write_atomic(path, &(serde_json::to_string_pretty(doc)? + "\n"))
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
@@ -1,3 +1,7 @@
+pub mod dedupe;
+pub mod install;
 pub mod server;
+pub mod session;
+pub mod validate;
 
 pub use server::MCPServer;
@@ -1,24 +1,130 @@
 use anyhow::Result;
 use serde_json::{json, Value};
+use std::cell::RefCell;
 use std::io::{self, BufRead, Write};
+use std::time::{Duration, Instant};
 
-use crate::core::{reader, writer};
+use super::dedupe::Dedupe;
+use super::session::{self, SessionState};
+use super::validate;
+use crate::core::timing;
+use crate::core::reader::MemoryFilter;
+use crate::core::writer::Provenance;
+use crate::core::{config, display, guard, reader, tokens, writer};
 
-pub struct MCPServer;
+const DEFAULT_INSTRUCTIONS: &str = include_str!("instructions.md");
+
+/// Protocol versions this server speaks, oldest first
+pub const PROTOCOL_VERSIONS: [&str; 3] = ["2024-11-05", "2025-03-26", "2025-06-18"];
+
+/// First version with structuredContent and outputSchema
+const STRUCTURED_SINCE: &str = "2025-06-18";
+
+/// Tools that delete records; hidden and refused in safe mode
+pub const DESTRUCTIVE_TOOLS: [&str; 1] = ["compress"];
+
+/// Tools that write records; hidden and refused in read-only mode
+pub const WRITE_TOOLS: [&str; 2] = ["save_memory", "compress"];
+
+pub struct MCPServer {
+    profile: bool,
+    safe_mode: bool,
+    /// clientInfo from initialize, stamped on records this session writes
+    client_info: RefCell<Option<Value>>,
+    state: RefCell<SessionState>,
+    started: Instant,
+    /// Version agreed in initialize; gates structured tool results
+    protocol: RefCell<&'static str>,
+    dedupe: RefCell<Dedupe>,
+}
+
+impl Default for MCPServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl MCPServer {
     pub fn new() -> Self {
-        MCPServer
+        MCPServer {
+            profile: false,
+            safe_mode: false,
+            client_info: RefCell::new(None),
+            state: RefCell::new(SessionState::default()),
+            started: Instant::now(),
+            protocol: RefCell::new(PROTOCOL_VERSIONS[0]),
+            dedupe: RefCell::new(Dedupe::default()),
+        }
     }
 
+    /// Attach `meta.duration_ms` to every tool response
+    pub fn profile(mut self, enabled: bool) -> Self {
+        self.profile = enabled;
+        self
+    }
+
+    /// Hide and refuse DESTRUCTIVE_TOOLS
+    pub fn safe_mode(mut self, enabled: bool) -> Self {
+        self.safe_mode = enabled;
+        self
+    }
+
+    /// Answer a repeated save_memory within `window` with the earlier
+    /// result, remembering up to `size` recent calls
+    pub fn dedupe(self, window: Duration, size: usize) -> Self {
+        *self.dedupe.borrow_mut() = Dedupe::new(window, size);
+        self
+    }
+
+    fn tools(&self) -> Vec<Value> {
+        let structured = self.structured();
+        tool_definitions()
+            .into_iter()
+            .filter(|t| self.disabled(t["name"].as_str().unwrap_or_default()).is_none())
+            .map(|mut t| {
+                if !structured {
+                    if let Some(obj) = t.as_object_mut() {
+                        obj.remove("outputSchema");
+                    }
+                }
+                t
+            })
+            .collect()
+    }
+
+    /// Why `tool` is unavailable in this server's mode, if it is
+    fn disabled(&self, tool: &str) -> Option<&'static str> {
+        if config::read_only() && WRITE_TOOLS.contains(&tool) {
+            Some("read-only mode")
+        } else if self.safe_mode && DESTRUCTIVE_TOOLS.contains(&tool) {
+            Some("safe mode")
+        } else {
+            None
+        }
+    }
+
+    fn structured(&self) -> bool {
+        *self.protocol.borrow() >= STRUCTURED_SINCE
+    }
+
+    /// Serve MCP over stdin/stdout until stdin closes
     pub fn run(&self) -> Result<()> {
         let stdin = io::stdin();
-        let mut stdout = io::stdout();
+        self.serve(stdin.lock(), io::stdout())
+    }
 
-        let reader = stdin.lock();
-        let lines = reader.lines();
+    /// Serve line-delimited JSON-RPC from `input`, writing responses to
+    /// `output`, until `input` ends or the client sends shutdown or exit
+    pub fn serve(&self, input: impl BufRead, mut output: impl Write) -> Result<()> {
+        {
+            let mut state = self.state.borrow_mut();
+            *state = SessionState::load(&session::state_file());
+            state.starts += 1;
+            state.started_at = Some(display::now());
+        }
+        self.save_state();
 
-        for line_result in lines {
+        for line_result in input.lines() {
             match line_result {
                 Ok(line) => {
                     let trimmed = line.trim().to_string();
@@ -26,12 +132,34 @@ impl MCPServer {
                         continue;
                     }
 
-                    if let Ok(request) = serde_json::from_str::<Value>(&trimmed) {
-                        let response = self.handle_request(request);
+                    let mut done = false;
+                    let response = match serde_json::from_str::<Value>(&trimmed) {
+                        // a batch is answered element by element, in one array
+                        Ok(Value::Array(batch)) if !batch.is_empty() => {
+                            let responses: Vec<Value> = batch
+                                .into_iter()
+                                .filter_map(|message| self.handle_message(message, &mut done))
+                                .collect();
+                            (!responses.is_empty()).then_some(Value::Array(responses))
+                        }
+                        Ok(message) => self.handle_message(message, &mut done),
+                        Err(_) => Some(json!({
+                            "jsonrpc": "2.0",
+                            "id": null,
+                            "error": {
+                                "code": -32700,
+                                "message": "Parse error"
+                            }
+                        })),
+                    };
+                    if let Some(response) = response {
                         let response_str = serde_json::to_string(&response)?;
-                        stdout.write_all(response_str.as_bytes())?;
-                        stdout.write_all(b"\n")?;
-                        stdout.flush()?;
+                        output.write_all(response_str.as_bytes())?;
+                        output.write_all(b"\n")?;
+                        output.flush()?;
+                    }
+                    if done {
+                        break;
                     }
                 }
                 Err(_) => break,
@@ -41,14 +169,37 @@ impl MCPServer {
         Ok(())
     }
 
+    /// Answer one JSON-RPC message. Notifications (objects without an
+    /// id) get None; anything that is not an object is an invalid
+    /// request. Sets `done` on shutdown and exit.
+    fn handle_message(&self, message: Value, done: &mut bool) -> Option<Value> {
+        if !message.is_object() {
+            return Some(json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": {
+                    "code": -32600,
+                    "message": "Invalid Request"
+                }
+            }));
+        }
+        if message.get("id").is_none() {
+            *done |= message["method"] == "exit";
+            return None;
+        }
+        *done |= message["method"] == "shutdown";
+        Some(self.handle_request(message))
+    }
+
     fn handle_request(&self, request: Value) -> Value {
         let method = request["method"].as_str().unwrap_or("");
         let id = request["id"].clone();
 
         match method {
-            "initialize" => self.handle_initialize(id),
+            "initialize" => self.handle_initialize(&request, id),
             "tools/list" => self.handle_tools_list(id),
             "tools/call" => self.handle_tools_call(request, id),
+            "ping" | "shutdown" => json!({ "jsonrpc": "2.0", "id": id, "result": {} }),
             _ => json!({
                 "jsonrpc": "2.0",
                 "id": id,
@@ -60,15 +211,33 @@ impl MCPServer {
         }
     }
 
-    fn handle_initialize(&self, id: Value) -> Value {
+    fn handle_initialize(&self, request: &Value, id: Value) -> Value {
+        let client = &request["params"]["clientInfo"];
+        *self.client_info.borrow_mut() = client.is_object().then(|| client.clone());
+        if client.is_object() {
+            self.state.borrow_mut().last_client = Some(client.clone());
+            self.save_state();
+        }
+
+        // echo a supported version, otherwise offer the newest we have
+        let requested = request["params"]["protocolVersion"].as_str().unwrap_or("");
+        let protocol = PROTOCOL_VERSIONS
+            .iter()
+            .find(|v| **v == requested)
+            .copied()
+            .unwrap_or(PROTOCOL_VERSIONS[PROTOCOL_VERSIONS.len() - 1]);
+        *self.protocol.borrow_mut() = protocol;
+
         let instructions = self.build_instructions();
         json!({
             "jsonrpc": "2.0",
             "id": id,
             "result": {
-                "protocolVersion": "2024-11-05",
+                "protocolVersion": protocol,
                 "capabilities": {
-                    "tools": {}
+                    "tools": {
+                        "listChanged": false
+                    }
                 },
                 "serverInfo": {
                     "name": "aigpt",
@@ -82,6 +251,15 @@ impl MCPServer {
     fn build_instructions(&self) -> String {
         let mut parts = Vec::new();
 
+        let template = std::fs::read_to_string(config::instructions_file())
+            .unwrap_or_else(|_| DEFAULT_INSTRUCTIONS.to_string());
+        let guide = template
+            .replace("{count}", &reader::memory_count().to_string())
+            .replace("{max}", &config::load().memory.to_string());
+        if !guide.trim().is_empty() {
+            parts.push(guide.trim().to_string());
+        }
+
         if let Ok(core) = reader::read_core() {
             if let Some(text) = core["value"]["content"]["text"].as_str() {
                 if !text.is_empty() {
@@ -91,71 +269,22 @@ impl MCPServer {
         }
 
         let records = reader::read_memory_all().unwrap_or_default();
-        for record in &records {
-            if let Some(text) = record["value"]["content"]["text"].as_str() {
-                if !text.is_empty() {
-                    parts.push(text.to_string());
-                }
-            }
+        let texts = records
+            .iter()
+            .filter_map(|r| r["value"]["content"]["text"].as_str());
+        if let Some(quoted) = guard::quote_memories(texts) {
+            parts.push(quoted);
         }
 
         parts.join("\n\n")
     }
 
     fn handle_tools_list(&self, id: Value) -> Value {
-        let tools = vec![
-            json!({
-                "name": "read_core",
-                "description": "Read the AI's identity and instructions (core record)",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {}
-                }
-            }),
-            json!({
-                "name": "read_memory",
-                "description": "Read all memory records. Each record is a single memory element.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {}
-                }
-            }),
-            json!({
-                "name": "save_memory",
-                "description": "Add a single memory element as a new record",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "content": {
-                            "type": "string",
-                            "description": "A single memory element to save"
-                        }
-                    },
-                    "required": ["content"]
-                }
-            }),
-            json!({
-                "name": "compress",
-                "description": "Replace all memory records with a compressed set. Deletes all existing records and creates new ones from the provided items.",
-                "inputSchema": {
-                    "type": "object",
-                    "properties": {
-                        "items": {
-                            "type": "array",
-                            "items": { "type": "string" },
-                            "description": "Array of memory elements to keep after compression"
-                        }
-                    },
-                    "required": ["items"]
-                }
-            }),
-        ];
-
         json!({
             "jsonrpc": "2.0",
             "id": id,
             "result": {
-                "tools": tools
+                "tools": self.tools()
             }
         })
     }
@@ -163,18 +292,44 @@ impl MCPServer {
     fn handle_tools_call(&self, request: Value, id: Value) -> Value {
         let tool_name = request["params"]["name"].as_str().unwrap_or("");
         let arguments = &request["params"]["arguments"];
+        let start = self.profile.then(Instant::now);
 
-        let result = match tool_name {
-            "read_core" => self.tool_read_core(),
-            "read_memory" => self.tool_read_memory(),
-            "save_memory" => self.tool_save_memory(arguments),
-            "compress" => self.tool_compress(arguments),
-            _ => json!({
-                "error": format!("Unknown tool: {}", tool_name)
-            }),
+        let schema = self
+            .tools()
+            .into_iter()
+            .find(|t| t["name"] == tool_name)
+            .map(|t| t["inputSchema"].clone());
+        let invalid = schema.and_then(|schema| validate::validate(&schema, arguments).err());
+
+        let result = if let Some(mode) = self.disabled(tool_name) {
+            json!({ "error": format!("Tool disabled in {}: {}", mode, tool_name) })
+        } else if let Some(reason) = invalid {
+            json!({ "error": format!("VALIDATION: {}", reason) })
+        } else {
+            match tool_name {
+                "read_core" => self.tool_read_core(),
+                "read_memory" => self.tool_read_memory(arguments),
+                "save_memory" => self.tool_save_memory(arguments),
+                "compress" => self.tool_compress(arguments),
+                "estimate_tokens" => self.tool_estimate_tokens(arguments),
+                "count_memories" => self.tool_count_memories(arguments),
+                "memory_exists" => self.tool_memory_exists(arguments),
+                "get_server_info" => self.tool_get_server_info(),
+                _ => json!({
+                    "error": format!("Unknown tool: {}", tool_name)
+                }),
+            }
         };
 
-        json!({
+        {
+            let mut state = self.state.borrow_mut();
+            state.tool_calls += 1;
+            state.last_tool = Some(tool_name.to_string());
+            state.last_tool_at = Some(display::now());
+        }
+        self.save_state();
+
+        let mut response = json!({
             "jsonrpc": "2.0",
             "id": id,
             "result": {
@@ -183,7 +338,28 @@ impl MCPServer {
                     "text": result.to_string()
                 }]
             }
-        })
+        });
+        if result.get("error").is_some() {
+            response["result"]["isError"] = json!(true);
+        } else if self.structured() && result.is_object() {
+            response["result"]["structuredContent"] = result;
+        }
+        if let Some(start) = start {
+            response["result"]["meta"] = json!({ "duration_ms": timing::ms(start.elapsed()) });
+        }
+        response
+    }
+
+    /// Best effort: a read-only data dir must not break tool calls
+    fn save_state(&self) {
+        if config::read_only() {
+            return;
+        }
+        let _ = self.state.borrow().save(&session::state_file());
+    }
+
+    fn provenance(&self) -> Provenance {
+        Provenance::mcp(self.client_info.borrow().clone())
     }
 
     fn tool_read_core(&self) -> Value {
@@ -193,22 +369,71 @@ impl MCPServer {
         }
     }
 
-    fn tool_read_memory(&self) -> Value {
-        match reader::read_memory_all() {
-            Ok(records) => json!({ "records": records, "count": records.len() }),
-            Err(e) => json!({ "error": e.to_string() }),
+    fn tool_read_memory(&self, arguments: &Value) -> Value {
+        let filter = match memory_filter(arguments) {
+            Ok(filter) => filter,
+            Err(e) => return e,
+        };
+        let mut records = match reader::read_memory_all() {
+            Ok(records) => records,
+            Err(e) => return json!({ "error": e.to_string() }),
+        };
+
+        records.retain(|r| filter.matches(r));
+        if arguments["order"] == "newest" {
+            records.reverse();
         }
+        let total = records.len();
+        let offset = arguments["offset"].as_u64().unwrap_or(0) as usize;
+        let limit = arguments["limit"].as_u64().map_or(usize::MAX, |n| n as usize);
+        let records: Vec<Value> = records.into_iter().skip(offset).take(limit).collect();
+
+        json!({
+            "records": records,
+            "count": records.len(),
+            "total": total,
+            "revision": reader::memory_revision()
+        })
     }
 
     fn tool_save_memory(&self, arguments: &Value) -> Value {
         let content = arguments["content"].as_str().unwrap_or("");
-        match writer::save_memory(content) {
-            Ok(()) => json!({ "success": true, "count": reader::memory_count() }),
+        let key = Dedupe::key("save_memory", content);
+        if arguments["allow_duplicate"] != true {
+            if let Some(mut prior) = self.dedupe.borrow_mut().lookup(key, Instant::now()) {
+                self.state.borrow_mut().deduplicated += 1;
+                prior["deduplicated"] = json!(true);
+                return prior;
+            }
+        }
+        match writer::save_memory(content, &self.provenance()) {
+            Ok(report) => {
+                let mut result = json!({ "success": true, "count": reader::memory_count() });
+                if report.truncated {
+                    result["truncated"] = json!(true);
+                }
+                if report.records > 1 {
+                    result["parts"] = json!(report.records);
+                }
+                self.dedupe.borrow_mut().record(key, result.clone(), Instant::now(), &report.uris);
+                result
+            }
             Err(e) => json!({ "error": e.to_string() }),
         }
     }
 
     fn tool_compress(&self, arguments: &Value) -> Value {
+        if let Some(expected) = arguments["expected_revision"].as_str() {
+            let current = reader::memory_revision();
+            if expected != current {
+                let records = reader::read_memory_all().unwrap_or_default();
+                return json!({
+                    "error": format!("CONFLICT: memory changed since revision {}; merge and retry", expected),
+                    "revision": current,
+                    "records": records
+                });
+            }
+        }
         let items: Vec<String> = arguments["items"]
             .as_array()
             .map(|arr| {
@@ -218,9 +443,245 @@ impl MCPServer {
             })
             .unwrap_or_default();
 
-        match writer::compress_memory(&items) {
-            Ok(()) => json!({ "success": true, "count": items.len() }),
+        match writer::compress_memory(&items, &self.provenance()) {
+            Ok(count) => {
+                self.dedupe.borrow_mut().clear();
+                json!({ "success": true, "count": count })
+            }
             Err(e) => json!({ "error": e.to_string() }),
         }
     }
+
+    fn tool_get_server_info(&self) -> Value {
+        let state = self.state.borrow();
+        json!({
+            "name": "aigpt",
+            "version": env!("CARGO_PKG_VERSION"),
+            "uptime_secs": self.started.elapsed().as_secs(),
+            "restarts": state.starts.saturating_sub(1),
+            "state": *state,
+        })
+    }
+
+    fn tool_count_memories(&self, arguments: &Value) -> Value {
+        let filter = match memory_filter(arguments) {
+            Ok(filter) => filter,
+            Err(e) => return e,
+        };
+        match reader::read_memory_all() {
+            Ok(records) => json!({ "count": records.iter().filter(|r| filter.matches(r)).count() }),
+            Err(e) => json!({ "error": e.to_string() }),
+        }
+    }
+
+    fn tool_memory_exists(&self, arguments: &Value) -> Value {
+        let content = arguments["content"].as_str().unwrap_or("").trim();
+        let records = match reader::read_memory_all() {
+            Ok(records) => records,
+            Err(e) => return json!({ "error": e.to_string() }),
+        };
+        let found = records
+            .iter()
+            .find(|r| r["value"]["content"]["text"].as_str().map(str::trim) == Some(content));
+        match found {
+            Some(r) => json!({ "exists": true, "uri": r["uri"] }),
+            None => json!({ "exists": false }),
+        }
+    }
+
+    fn tool_estimate_tokens(&self, arguments: &Value) -> Value {
+        let text = arguments["text"].as_str().unwrap_or("");
+        json!({ "tokens": tokens::estimate(text), "chars": text.chars().count() })
+    }
+}
+
+fn tool_definitions() -> Vec<Value> {
+    let mut read_props = filter_properties();
+    read_props["order"] = json!({
+        "type": "string",
+        "enum": ["oldest", "newest"],
+        "description": "Sort by creation time (default: oldest)"
+    });
+    read_props["offset"] = json!({
+        "type": "integer",
+        "minimum": 0,
+        "description": "Records to skip"
+    });
+    read_props["limit"] = json!({
+        "type": "integer",
+        "minimum": 1,
+        "description": "Maximum records to return"
+    });
+
+    vec![
+        json!({
+            "name": "read_core",
+            "description": "Read the AI's identity and instructions (core record)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {}
+            }
+        }),
+        json!({
+            "name": "read_memory",
+            "description": "Read all memory records. Each record is a single memory element. Optional arguments page and filter the result; total is the match count before paging. revision identifies the current record set for compress.",
+            "inputSchema": {
+                "type": "object",
+                "properties": read_props
+            },
+            "outputSchema": {
+                "type": "object",
+                "properties": {
+                    "records": { "type": "array", "items": { "type": "object" } },
+                    "count": { "type": "integer" },
+                    "total": { "type": "integer" },
+                    "revision": { "type": "string" }
+                },
+                "required": ["records", "count", "total", "revision"]
+            }
+        }),
+        json!({
+            "name": "save_memory",
+            "description": "Add a single memory element as a new record",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": {
+                        "type": "string",
+                        "minLength": 1,
+                        "description": "A single memory element to save"
+                    },
+                    "allow_duplicate": {
+                        "type": "boolean",
+                        "description": "Save even if the same content was saved moments ago; by default a repeat returns the earlier result with deduplicated: true"
+                    }
+                },
+                "required": ["content"]
+            }
+        }),
+        json!({
+            "name": "compress",
+            "description": "Replace all memory records with a compressed set. Deletes all existing records and creates new ones from the provided items.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "items": {
+                        "type": "array",
+                        "items": { "type": "string", "minLength": 1 },
+                        "description": "Array of memory elements to keep after compression"
+                    },
+                    "expected_revision": {
+                        "type": "string",
+                        "description": "revision from the read_memory this compress is based on; if records were added or removed since, nothing is deleted and a CONFLICT error returns the current records"
+                    }
+                },
+                "required": ["items"]
+            }
+        }),
+        json!({
+            "name": "count_memories",
+            "description": "Count memory records without reading them. Takes the same filters as read_memory.",
+            "inputSchema": {
+                "type": "object",
+                "properties": filter_properties()
+            },
+            "outputSchema": {
+                "type": "object",
+                "properties": {
+                    "count": { "type": "integer" }
+                },
+                "required": ["count"]
+            }
+        }),
+        json!({
+            "name": "memory_exists",
+            "description": "Check whether a memory with exactly this content (ignoring surrounding whitespace) is already saved",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "content": {
+                        "type": "string",
+                        "minLength": 1,
+                        "description": "Content to look for"
+                    }
+                },
+                "required": ["content"]
+            },
+            "outputSchema": {
+                "type": "object",
+                "properties": {
+                    "exists": { "type": "boolean" },
+                    "uri": { "type": "string" }
+                },
+                "required": ["exists"]
+            }
+        }),
+        json!({
+            "name": "get_server_info",
+            "description": "Server version, uptime, restart count, and session state persisted across restarts (last client, tool call counters)",
+            "inputSchema": {
+                "type": "object",
+                "properties": {}
+            }
+        }),
+        json!({
+            "name": "estimate_tokens",
+            "description": "Estimate the token count of a text, e.g. to size memory items before compress",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "text": {
+                        "type": "string",
+                        "description": "Text to estimate"
+                    }
+                },
+                "required": ["text"]
+            },
+            "outputSchema": {
+                "type": "object",
+                "properties": {
+                    "tokens": { "type": "integer" },
+                    "chars": { "type": "integer" }
+                },
+                "required": ["tokens", "chars"]
+            }
+        }),
+    ]
+}
+
+/// Schema for the arguments read by `memory_filter`
+fn filter_properties() -> Value {
+    json!({
+        "since": {
+            "type": "string",
+            "description": "Only records created at or after this date (YYYY-MM-DD, RFC 3339, or relative like 30d)"
+        },
+        "query": {
+            "type": "string",
+            "description": "Only records whose content contains this text (case-insensitive)"
+        },
+        "created_by": {
+            "type": "string",
+            "description": "Only records written via this path (cli, mcp)"
+        }
+    })
+}
+
+fn memory_filter(arguments: &Value) -> Result<MemoryFilter, Value> {
+    let since = match arguments["since"].as_str() {
+        Some(s) => match reader::parse_since(s) {
+            Some(since) => Some(since),
+            None => {
+                return Err(json!({
+                    "error": "VALIDATION: since: expected YYYY-MM-DD, RFC 3339, or Nd"
+                }))
+            }
+        },
+        None => None,
+    };
+    Ok(MemoryFilter {
+        since,
+        query: arguments["query"].as_str().map(str::to_string),
+        created_by: arguments["created_by"].as_str().map(str::to_string),
+    })
 }
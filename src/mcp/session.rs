@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Server state kept in `server.json` across restarts
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct SessionState {
+    /// Number of times the server has started, including this run
+    pub starts: u64,
+    pub started_at: Option<String>,
+    /// clientInfo from the most recent initialize
+    pub last_client: Option<Value>,
+    pub tool_calls: u64,
+    pub last_tool: Option<String>,
+    pub last_tool_at: Option<String>,
+    /// save_memory calls answered from the dedupe window instead of written
+    pub deduplicated: u64,
+}
+
+impl SessionState {
+    /// Missing or unreadable files start from zero
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        crate::core::writer::write_atomic(path, &json)
+    }
+}
+
+pub fn state_file() -> PathBuf {
+    let cfg = crate::core::config::load();
+    crate::core::config::base_dir(&cfg).join("server.json")
+}
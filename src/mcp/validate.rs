@@ -0,0 +1,85 @@
+use serde_json::Value;
+
+/// Check tool arguments against the subset of JSON Schema used by
+/// the tool definitions: type, required, properties, items, minLength.
+/// Returns the offending field and constraint on failure.
+pub fn validate(schema: &Value, args: &Value) -> Result<(), String> {
+    let empty = Value::Object(Default::default());
+    let args = if args.is_null() { &empty } else { args };
+    check(schema, args, "arguments")
+}
+
+fn check(schema: &Value, value: &Value, field: &str) -> Result<(), String> {
+    if let Some(ty) = schema["type"].as_str() {
+        if !type_matches(ty, value) {
+            return Err(format!("{}: expected {}, got {}", field, ty, type_name(value)));
+        }
+    }
+
+    if let (Some(min), Some(s)) = (schema["minLength"].as_u64(), value.as_str()) {
+        if (s.trim().chars().count() as u64) < min {
+            return Err(format!("{}: must not be empty", field));
+        }
+    }
+
+    if let (Some(min), Some(n)) = (schema["minimum"].as_f64(), value.as_f64()) {
+        if n < min {
+            return Err(format!("{}: must be >= {}", field, min));
+        }
+    }
+
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.contains(value) {
+            let names: Vec<String> = allowed.iter().map(|v| v.to_string()).collect();
+            return Err(format!("{}: must be one of {}", field, names.join(", ")));
+        }
+    }
+
+    if let Some(obj) = value.as_object() {
+        if let Some(required) = schema["required"].as_array() {
+            for name in required.iter().filter_map(|r| r.as_str()) {
+                if !obj.contains_key(name) {
+                    return Err(format!("{}: required", name));
+                }
+            }
+        }
+        if let Some(props) = schema["properties"].as_object() {
+            for (name, prop) in props {
+                if let Some(v) = obj.get(name) {
+                    check(prop, v, name)?;
+                }
+            }
+        }
+    }
+
+    if let (Some(items), Some(arr)) = (schema.get("items"), value.as_array()) {
+        for (i, v) in arr.iter().enumerate() {
+            check(items, v, &format!("{}[{}]", field, i))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn type_matches(ty: &str, value: &Value) -> bool {
+    match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_u64() || value.is_i64(),
+        "number" => value.is_number(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::{run, TestDir};
+
+#[test]
+fn save_aliases() {
+    let dir = TestDir::new();
+    for args in [
+        &["save-memory", "a"][..],
+        &["save", "b"],
+        &["new", "c"],
+        &["memory", "save", "d"],
+        &["memory", "new", "e"],
+    ] {
+        assert!(run(&dir, args).starts_with("Saved."), "{:?}", args);
+    }
+    assert_eq!(dir.memory_files().len(), 5);
+}
+
+#[test]
+fn read_aliases_match() {
+    let dir = TestDir::new();
+    run(&dir, &["save-memory", "hello"]);
+    let expected = run(&dir, &["read-memory"]);
+    assert!(expected.contains("hello"));
+    for args in [&["ls"][..], &["list"], &["memory", "list"], &["memory", "ls"]] {
+        assert_eq!(run(&dir, args), expected, "{:?}", args);
+    }
+    assert_eq!(run(&dir, &["core"]), run(&dir, &["read-core"]));
+}
+
+#[test]
+fn aliases_shown_in_help() {
+    let dir = TestDir::new();
+    let help = run(&dir, &["--help"]);
+    assert!(help.contains("[aliases: ls, list]"));
+    assert!(help.contains("[aliases: new, save]"));
+    assert!(help.contains("[alias: core]"));
+}
+
+#[test]
+fn status_groups_digits_per_config() {
+    let dir = TestDir::new();
+    dir.write_config(serde_json::json!({ "memory": 1500 }));
+    assert!(run(&dir, &[]).contains("memory: 1500\n"));
+
+    dir.write_config(serde_json::json!({ "memory": 1500, "thousands": "comma" }));
+    let status = run(&dir, &[]);
+    assert!(status.contains("memory: 1,500\n"), "{}", status);
+    assert!(status.contains("records: 0/1,500\n"), "{}", status);
+}
+
+#[test]
+fn stale_temp_files_are_removed() {
+    let dir = TestDir::new();
+    run(&dir, &["save", "kept"]);
+    let stale = dir.memory_dir().join(".3abc.json.4242-0.tmp");
+    let fresh = dir.memory_dir().join(".3abd.json.4242-1.tmp");
+    for path in [&stale, &fresh] {
+        std::fs::write(path, "{").unwrap();
+    }
+    let old = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+    std::fs::File::options().write(true).open(&stale).unwrap().set_modified(old).unwrap();
+
+    run(&dir, &["save", "again"]);
+    assert!(!stale.exists());
+    assert!(fresh.exists(), "a temp file another writer may still be using is kept");
+    let names: Vec<_> = std::fs::read_dir(dir.memory_dir())
+        .unwrap()
+        .flatten()
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|n| n.ends_with(".tmp"))
+        .collect();
+    assert_eq!(names, [".3abd.json.4242-1.tmp"]);
+    assert_eq!(dir.memory_files().len(), 2);
+}
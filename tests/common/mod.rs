@@ -0,0 +1,206 @@
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_DIR: AtomicU64 = AtomicU64::new(0);
+
+/// Scratch dir holding both the config and the data dir of one test
+pub struct TestDir {
+    pub path: PathBuf,
+}
+
+impl TestDir {
+    pub fn new() -> Self {
+        let path = std::env::temp_dir().join(format!(
+            "aigpt-test-{}-{}",
+            std::process::id(),
+            NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TestDir { path }
+    }
+
+    pub fn config_home(&self) -> PathBuf {
+        self.path.join("config")
+    }
+
+    pub fn data_dir(&self) -> PathBuf {
+        self.path.join("data")
+    }
+
+    /// `aigpt` with config and data isolated in this dir
+    pub fn command(&self) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_aigpt"));
+        cmd.env("XDG_CONFIG_HOME", self.config_home())
+            .env("HOME", &self.path)
+            .env_remove("AIGPT_DATA_DIR")
+            .arg("--data-dir")
+            .arg(self.data_dir());
+        cmd
+    }
+
+    /// Write config.json with the given `bot` section
+    pub fn write_config(&self, bot: Value) {
+        let dir = self.config_home().join("ai.syui.gpt");
+        std::fs::create_dir_all(&dir).unwrap();
+        let cfg = json!({ "bot": bot });
+        std::fs::write(dir.join("config.json"), cfg.to_string()).unwrap();
+    }
+
+    pub fn memory_dir(&self) -> PathBuf {
+        self.data_dir().join("self").join("ai.syui.gpt.memory")
+    }
+
+    pub fn memory_files(&self) -> Vec<PathBuf> {
+        json_files(&self.memory_dir())
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+pub fn json_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<_> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Run `aigpt` in `dir`, asserting success, and return its stdout
+pub fn run(dir: &TestDir, args: &[&str]) -> String {
+    let out = dir.command().args(args).output().unwrap();
+    assert!(out.status.success(), "{:?}: {}", args, String::from_utf8_lossy(&out.stderr));
+    String::from_utf8(out.stdout).unwrap()
+}
+
+/// Content text of every memory record file, oldest first
+pub fn texts(dir: &TestDir) -> Vec<String> {
+    dir.memory_files()
+        .iter()
+        .map(|p| {
+            let record: Value =
+                serde_json::from_str(&std::fs::read_to_string(p).unwrap()).unwrap();
+            record["value"]["content"]["text"].as_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+/// Line-delimited JSON-RPC client driving `aigpt server` over stdio
+pub struct McpClient {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl McpClient {
+    pub fn start(dir: &TestDir) -> Self {
+        Self::start_with(dir, &[])
+    }
+
+    pub fn start_with(dir: &TestDir, args: &[&str]) -> Self {
+        let mut child = dir
+            .command()
+            .arg("server")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("failed to spawn aigpt server");
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        McpClient { child, stdin, stdout, next_id: 1 }
+    }
+
+    /// initialize + notifications/initialized, returning the initialize result
+    pub fn handshake(&mut self) -> Value {
+        let result = self.request(
+            "initialize",
+            json!({
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "aigpt-test", "version": "0.0.0" }
+            }),
+        );
+        self.notify("notifications/initialized", json!({}));
+        result["result"].clone()
+    }
+
+    pub fn send_raw(&mut self, line: &str) {
+        self.stdin.write_all(line.as_bytes()).unwrap();
+        self.stdin.write_all(b"\n").unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    pub fn read_response(&mut self) -> Value {
+        let mut line = String::new();
+        let n = self.stdout.read_line(&mut line).unwrap();
+        assert!(n > 0, "server closed stdout");
+        serde_json::from_str(&line).expect("server wrote invalid JSON")
+    }
+
+    /// Send a request and return the full JSON-RPC response
+    pub fn request(&mut self, method: &str, params: Value) -> Value {
+        let id = self.next_id;
+        self.next_id += 1;
+        let msg = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        self.send_raw(&msg.to_string());
+        let response = self.read_response();
+        assert_eq!(response["id"], json!(id));
+        response
+    }
+
+    pub fn notify(&mut self, method: &str, params: Value) {
+        let msg = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        self.send_raw(&msg.to_string());
+    }
+
+    /// tools/call, returning the JSON decoded from the text content block
+    pub fn call_tool(&mut self, name: &str, arguments: Value) -> Value {
+        let response = self.request("tools/call", json!({ "name": name, "arguments": arguments }));
+        let text = response["result"]["content"][0]["text"]
+            .as_str()
+            .expect("tool result has no text content");
+        serde_json::from_str(text).expect("tool text is not JSON")
+    }
+
+    /// SIGTERM the server without closing stdin
+    #[cfg(unix)]
+    pub fn terminate(mut self) -> std::process::ExitStatus {
+        Command::new("kill")
+            .arg("-TERM")
+            .arg(self.child.id().to_string())
+            .status()
+            .unwrap();
+        self.child.wait().unwrap()
+    }
+
+    /// Wait for the server to exit on its own, keeping stdin open
+    pub fn wait(mut self) -> std::process::ExitStatus {
+        let status = self.child.wait().unwrap();
+        drop(self.stdin);
+        status
+    }
+
+    /// Close stdin and wait for the server to exit
+    pub fn finish(mut self) -> std::process::ExitStatus {
+        drop(self.stdin);
+        self.child.wait().unwrap()
+    }
+}
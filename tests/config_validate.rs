@@ -0,0 +1,68 @@
+mod common;
+
+use aigpt::core::config::{validate, Problem};
+use common::TestDir;
+
+#[test]
+fn valid_config_has_no_problems() {
+    let content = r#"{
+  "bot": {
+    "did": "did:plc:abc",
+    "handle": "ai.syui.ai",
+    "path": "~/ai/gpt",
+    "memory": 50,
+    "oversize": "chunk"
+  },
+  "site": { "title": "unrelated keys are fine" }
+}"#;
+    assert_eq!(validate(content), Vec::<Problem>::new());
+}
+
+#[test]
+fn misspelled_key_suggests_nearest() {
+    let content = "{\n  \"bot\": {\n    \"did\": null,\n    \"memroy\": 50\n  }\n}";
+    let problems = validate(content);
+    assert_eq!(
+        problems,
+        [Problem {
+            line: Some(4),
+            message: "unknown key bot.memroy (did you mean 'memory'?)".into()
+        }]
+    );
+}
+
+#[test]
+fn unrelated_bot_keys_are_ignored() {
+    assert!(validate(r#"{"bot": {"password_file": "x"}}"#).is_empty());
+}
+
+#[test]
+fn type_mismatch_names_field_and_line() {
+    let content = "{\n  \"bot\": {\n    \"memory\": \"100\",\n    \"oversize\": \"drop\"\n  }\n}";
+    let problems = validate(content);
+    assert_eq!(problems.len(), 2);
+    assert_eq!(problems[0].line, Some(3));
+    assert!(problems[0].message.starts_with("bot.memory must be a positive integer"));
+    assert_eq!(problems[1].line, Some(4));
+    assert!(problems[1].message.starts_with("bot.oversize must be one of"));
+}
+
+#[test]
+fn syntax_error_reports_line() {
+    let problems = validate("{\n  \"bot\": {\n    \"memory\": 5,\n  }\n}");
+    assert_eq!(problems.len(), 1);
+    assert_eq!(problems[0].line, Some(4));
+}
+
+#[test]
+fn cli_exit_status() {
+    let dir = TestDir::new();
+    dir.write_config(serde_json::json!({ "memory": 10 }));
+    let out = dir.command().args(["config", "validate"]).output().unwrap();
+    assert!(out.status.success());
+
+    dir.write_config(serde_json::json!({ "handel": "x" }));
+    let out = dir.command().args(["config", "validate"]).output().unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("did you mean 'handle'?"));
+}
@@ -0,0 +1,69 @@
+mod common;
+
+use common::{texts, McpClient, TestDir};
+use serde_json::{json, Value};
+
+fn start(oversize: &str, max: usize) -> (TestDir, McpClient) {
+    let dir = TestDir::new();
+    dir.write_config(json!({ "content_max": max, "oversize": oversize }));
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+    (dir, client)
+}
+
+#[test]
+fn reject_writes_nothing() {
+    let (dir, mut client) = start("reject", 16);
+    let result = client.call_tool("save_memory", json!({ "content": "a".repeat(17) }));
+    assert!(result["error"].as_str().unwrap().contains("over the 16 byte limit"));
+    assert!(dir.memory_files().is_empty());
+
+    let ok = client.call_tool("save_memory", json!({ "content": "a".repeat(16) }));
+    assert_eq!(ok, json!({ "success": true, "count": 1 }));
+}
+
+#[test]
+fn reject_in_compress_keeps_existing_records() {
+    let (dir, mut client) = start("reject", 16);
+    client.call_tool("save_memory", json!({ "content": "keep me" }));
+    let result = client.call_tool("compress", json!({ "items": ["short", "x".repeat(40)] }));
+    assert!(result["error"].is_string());
+    assert_eq!(texts(&dir), ["keep me"]);
+}
+
+#[test]
+fn truncate_on_char_boundary() {
+    let (dir, mut client) = start("truncate", 10);
+    // 4 x 3-byte chars: the limit falls inside the fourth
+    let result = client.call_tool("save_memory", json!({ "content": "あいうえ" }));
+    assert_eq!(result, json!({ "success": true, "count": 1, "truncated": true }));
+    assert_eq!(texts(&dir), ["あいう"]);
+}
+
+#[test]
+fn chunk_into_labelled_parts() {
+    let (dir, mut client) = start("chunk", 18);
+    let content = "first line\nsecond line\nthird line";
+    let result: Value = client.call_tool("save_memory", json!({ "content": content }));
+    assert_eq!(result["parts"], 3);
+
+    let parts = texts(&dir);
+    assert_eq!(parts, ["[1/3] first line\n", "[2/3] second line\n", "[3/3] third line"]);
+    assert!(parts.iter().all(|p| p.len() <= 18));
+
+    let stitched: String = parts.iter().map(|p| p.split_once("] ").unwrap().1).collect();
+    assert_eq!(stitched, content);
+}
+
+#[test]
+fn chunk_without_newlines_stays_under_limit() {
+    let (dir, mut client) = start("chunk", 12);
+    let content = "日本語のテキストを分割する";
+    client.call_tool("save_memory", json!({ "content": content }));
+
+    let parts = texts(&dir);
+    assert!(parts.len() > 1);
+    assert!(parts.iter().all(|p| p.len() <= 12), "{:?}", parts);
+    let stitched: String = parts.iter().map(|p| p.split_once("] ").unwrap().1).collect();
+    assert_eq!(stitched, content);
+}
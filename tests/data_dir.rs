@@ -0,0 +1,73 @@
+mod common;
+
+use common::{json_files, TestDir};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// `aigpt` isolated like `TestDir::command`, but with no --data-dir and
+/// AIGPT_DATA_DIR set only when given
+fn aigpt(dir: &TestDir, env: Option<&Path>) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_aigpt"));
+    cmd.env("XDG_CONFIG_HOME", dir.config_home()).env("HOME", &dir.path);
+    match env {
+        Some(path) => cmd.env("AIGPT_DATA_DIR", path),
+        None => cmd.env_remove("AIGPT_DATA_DIR"),
+    };
+    cmd
+}
+
+fn memory_files(base: &Path) -> Vec<PathBuf> {
+    json_files(&base.join("self").join("ai.syui.gpt.memory"))
+}
+
+/// Save one record and return the candidate dirs holding any records
+fn saved_in(cmd: &mut Command, candidates: &[&Path]) -> Vec<PathBuf> {
+    let out = cmd.args(["save", "where am i"]).output().unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    candidates
+        .iter()
+        .filter(|base| !memory_files(base).is_empty())
+        .map(|base| base.to_path_buf())
+        .collect()
+}
+
+#[test]
+fn data_dir_precedence() {
+    let dir = TestDir::new();
+    let flag = dir.path.join("flag");
+    let env = dir.path.join("env");
+    let config_path = dir.path.join("config-path");
+    let default = dir.config_home().join("ai.syui.gpt");
+    let all = [flag.as_path(), env.as_path(), config_path.as_path(), default.as_path()];
+    dir.write_config(json!({ "path": config_path }));
+
+    // --data-dir beats AIGPT_DATA_DIR and bot.path
+    let mut cmd = aigpt(&dir, Some(&env));
+    cmd.arg("--data-dir").arg(&flag);
+    assert_eq!(saved_in(&mut cmd, &all), &all[..1]);
+
+    // AIGPT_DATA_DIR beats bot.path
+    let mut cmd = aigpt(&dir, Some(&env));
+    assert_eq!(saved_in(&mut cmd, &all), &all[..2]);
+
+    // bot.path beats the default
+    let mut cmd = aigpt(&dir, None);
+    assert_eq!(saved_in(&mut cmd, &all), &all[..3]);
+
+    // with none of them, records go to the config dir
+    dir.write_config(json!({}));
+    let mut cmd = aigpt(&dir, None);
+    assert_eq!(saved_in(&mut cmd, &all), all);
+}
+
+#[test]
+fn empty_env_var_is_ignored() {
+    let dir = TestDir::new();
+    let config_path = dir.path.join("config-path");
+    dir.write_config(json!({ "path": config_path }));
+
+    let out = aigpt(&dir, Some(Path::new(""))).args(["save", "x"]).output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(memory_files(&config_path).len(), 1);
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use aigpt::core::config;
+use aigpt::mcp::dedupe::Dedupe;
+use aigpt::{save_memory, Provenance};
+use common::TestDir;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+#[test]
+fn repeat_within_window_returns_prior_result() {
+    let mut dedupe = Dedupe::new(Duration::from_secs(60), 8);
+    let t0 = Instant::now();
+    let key = Dedupe::key("save_memory", "likes rust");
+
+    assert_eq!(dedupe.lookup(key, t0), None);
+    dedupe.record(key, json!({ "success": true, "count": 1 }), t0, &[]);
+
+    let later = t0 + Duration::from_secs(59);
+    assert_eq!(dedupe.lookup(key, later), Some(json!({ "success": true, "count": 1 })));
+    // surrounding whitespace does not make a call distinct
+    let padded = Dedupe::key("save_memory", " likes rust\n");
+    assert!(dedupe.lookup(padded, later).is_some());
+    assert_eq!(dedupe.lookup(Dedupe::key("save_memory", "likes go"), later), None);
+}
+
+#[test]
+fn entries_expire_after_window() {
+    let mut dedupe = Dedupe::new(Duration::from_secs(60), 8);
+    let t0 = Instant::now();
+    let key = Dedupe::key("save_memory", "likes rust");
+    dedupe.record(key, json!({ "success": true }), t0, &[]);
+
+    assert_eq!(dedupe.lookup(key, t0 + Duration::from_secs(60)), None);
+    // expired entries are dropped, not revived by an earlier clock
+    assert_eq!(dedupe.lookup(key, t0), None);
+}
+
+#[test]
+fn oldest_entries_are_evicted_beyond_size() {
+    let mut dedupe = Dedupe::new(Duration::from_secs(60), 2);
+    let t0 = Instant::now();
+    let keys: Vec<u64> = ["a", "b", "c"].iter().map(|c| Dedupe::key("save_memory", c)).collect();
+    for key in &keys {
+        dedupe.record(*key, json!({}), t0, &[]);
+    }
+
+    assert_eq!(dedupe.lookup(keys[0], t0), None);
+    assert!(dedupe.lookup(keys[1], t0).is_some());
+    assert!(dedupe.lookup(keys[2], t0).is_some());
+}
+
+#[test]
+fn zero_window_disables() {
+    let mut dedupe = Dedupe::new(Duration::ZERO, 8);
+    let t0 = Instant::now();
+    let key = Dedupe::key("save_memory", "likes rust");
+    dedupe.record(key, json!({}), t0, &[]);
+    assert!(!dedupe.enabled());
+    assert_eq!(dedupe.lookup(key, t0), None);
+}
+
+#[test]
+fn changed_or_removed_record_skips_prior_result() {
+    let dir = TestDir::new();
+    config::set_data_dir(dir.data_dir());
+    let mut dedupe = Dedupe::new(Duration::from_secs(60), 8);
+    let t0 = Instant::now();
+    let key = Dedupe::key("save_memory", "likes rust");
+    let report = save_memory("likes rust", &Provenance::cli()).unwrap();
+    dedupe.record(key, json!({ "success": true }), t0, &report.uris);
+
+    // other writes in between do not matter
+    save_memory("likes go", &Provenance::cli()).unwrap();
+    assert!(dedupe.lookup(key, t0).is_some());
+    dedupe.clear();
+    assert_eq!(dedupe.lookup(key, t0), None);
+
+    dedupe.record(key, json!({ "success": true }), t0, &report.uris);
+    let path = &dir.memory_files()[0];
+    let edited = std::fs::read_to_string(path).unwrap().replace("likes rust", "likes c");
+    std::fs::write(path, edited).unwrap();
+    assert_eq!(dedupe.lookup(key, t0), None);
+
+    std::fs::remove_file(path).unwrap();
+    dedupe.record(key, json!({ "success": true }), t0, &report.uris);
+    assert_eq!(dedupe.lookup(key, t0), None, "a missing record is not remembered");
+}
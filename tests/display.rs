@@ -0,0 +1,52 @@
+use aigpt::core::display::{self, Thousands};
+use chrono::{TimeZone, Utc};
+
+/// Group by repeated division, as a check on the string-walking version
+fn reference(mut n: u64, sep: &str) -> String {
+    let mut groups = Vec::new();
+    while n >= 1000 {
+        groups.push(format!("{:03}", n % 1000));
+        n /= 1000;
+    }
+    groups.push(n.to_string());
+    groups.reverse();
+    groups.join(sep)
+}
+
+#[test]
+fn number_matches_reference() {
+    let mut samples: Vec<u64> = (0..20_000).collect();
+    // boundaries around every power of ten up to 10^12
+    let mut p = 10u64;
+    while p <= 1_000_000_000_000 {
+        samples.extend([p - 1, p, p + 1]);
+        p *= 10;
+    }
+    // spread across the range with a fixed LCG
+    let mut x = 0x2545_f491_4f6c_dd1du64;
+    for _ in 0..20_000 {
+        x = x.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        samples.push(x % 1_000_000_000_001);
+    }
+
+    for n in samples {
+        assert_eq!(display::number(n, Thousands::Comma), reference(n, ","), "{}", n);
+        assert_eq!(display::number(n, Thousands::Space), reference(n, " "), "{}", n);
+        assert_eq!(display::number(n, Thousands::None), n.to_string());
+    }
+}
+
+#[test]
+fn number_examples() {
+    assert_eq!(display::number(0, Thousands::Comma), "0");
+    assert_eq!(display::number(999, Thousands::Comma), "999");
+    assert_eq!(display::number(1000, Thousands::Comma), "1,000");
+    assert_eq!(display::number(1_234_567, Thousands::Space), "1 234 567");
+    assert_eq!(display::number(u64::MAX, Thousands::Comma), "18,446,744,073,709,551,615");
+}
+
+#[test]
+fn timestamp_format() {
+    let t = Utc.with_ymd_and_hms(2026, 1, 2, 3, 4, 5).unwrap();
+    assert_eq!(display::timestamp(t), "2026-01-02T03:04:05Z");
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use aigpt::core::display;
+use aigpt::core::reader::{parse_record, Damage};
+use common::{run, TestDir};
+
+const FAMILY: &str = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+
+fn record_bytes(tid: &str, text_json: &[u8]) -> Vec<u8> {
+    let mut out = format!(
+        r#"{{"uri":"at://self/ai.syui.gpt.memory/{}","value":{{"$type":"ai.syui.gpt.memory","content":{{"text":"#,
+        tid
+    )
+    .into_bytes();
+    out.extend_from_slice(text_json);
+    out.extend_from_slice(br#"},"createdAt":"2026-01-05T09:00:00Z"}}"#);
+    out
+}
+
+#[test]
+fn parse_reads_damage_lossily() {
+    let (record, damage) = parse_record(&record_bytes("a", b"\"bad \xff byte\"")).unwrap();
+    assert_eq!(damage, Some(Damage::InvalidUtf8));
+    assert_eq!(record["value"]["content"]["text"], "bad \u{FFFD} byte");
+
+    let (record, damage) = parse_record(&record_bytes("b", br#""half \ud83d pair""#)).unwrap();
+    assert_eq!(damage, Some(Damage::LoneSurrogate));
+    assert_eq!(record["value"]["content"]["text"], "half \u{FFFD} pair");
+
+    // a proper pair and an escaped backslash are left alone
+    let pair = br#""\ud83d\ude00 \\ud800""#;
+    let (record, damage) = parse_record(&record_bytes("c", pair)).unwrap();
+    assert_eq!(damage, None);
+    assert_eq!(record["value"]["content"]["text"], "\u{1F600} \\ud800");
+
+    let old_import = "\"old \u{FFFD} import\"";
+    let (_, damage) = parse_record(&record_bytes("d", old_import.as_bytes())).unwrap();
+    assert_eq!(damage, Some(Damage::ReplacementChar));
+
+    assert!(parse_record(b"{not json").is_err());
+}
+
+#[test]
+fn truncation_keeps_clusters_whole() {
+    let text = format!("ab{}cd", FAMILY);
+    // FAMILY is five chars; cutting anywhere inside it backs off before it
+    for max in 3..7 {
+        assert_eq!(display::truncate(&text, max), ("ab", true), "{}", max);
+    }
+    assert_eq!(display::truncate(&text, 7), (&text[..text.len() - 2], true));
+    assert_eq!(display::truncate(&text, 9), (text.as_str(), false));
+
+    let flags = "\u{1F1EF}\u{1F1F5}\u{1F1FA}\u{1F1F8}";
+    assert_eq!(display::truncate(flags, 3), ("\u{1F1EF}\u{1F1F5}", true));
+    assert_eq!(display::truncate("e\u{301}x", 1), ("", true));
+}
+
+#[test]
+fn oversize_truncate_does_not_split_emoji() {
+    let dir = TestDir::new();
+    dir.write_config(serde_json::json!({ "content_max": 10, "oversize": "truncate" }));
+    run(&dir, &["save", &format!("hi {}", FAMILY)]);
+    let out = run(&dir, &["ls"]);
+    assert!(out.contains("\"text\": \"hi \""), "{}", out);
+}
+
+#[test]
+fn damaged_records_are_listed_and_repaired() {
+    let dir = TestDir::new();
+    run(&dir, &["save", "fine"]);
+    let memory = dir.memory_dir();
+    let write = |tid: &str, text_json: &[u8]| {
+        std::fs::write(memory.join(format!("{}.json", tid)), record_bytes(tid, text_json)).unwrap();
+    };
+    write("3zzzzzzzzzzz2", b"\"bad \xff\"");
+    write("3zzzzzzzzzzz3", br#""x \udc00""#);
+    write("3zzzzzzzzzzz4", format!("\"{} \u{FFFD}\"", FAMILY).as_bytes());
+
+    // damaged files no longer break reads
+    assert!(run(&dir, &["ls"]).contains("bad \u{FFFD}"));
+    assert_eq!(run(&dir, &["count"]), "4\n");
+
+    let before = std::fs::read(memory.join("3zzzzzzzzzzz2.json")).unwrap();
+    let out = run(&dir, &["repair", "encoding", "--dry-run"]);
+    assert_eq!(
+        out,
+        "\
+at://self/ai.syui.gpt.memory/3zzzzzzzzzzz2  invalid UTF-8
+at://self/ai.syui.gpt.memory/3zzzzzzzzzzz3  lone surrogate escape
+at://self/ai.syui.gpt.memory/3zzzzzzzzzzz4  replacement character (U+FFFD)
+3 records with encoding problems (dry run)
+"
+    );
+    assert_eq!(std::fs::read(memory.join("3zzzzzzzzzzz2.json")).unwrap(), before);
+
+    let out = run(&dir, &["repair", "encoding"]);
+    assert!(out.ends_with("3 records with encoding problems, 2 rewritten\n"), "{}", out);
+    assert_eq!(std::fs::read(memory.join("3zzzzzzzzzzz2.json.bak")).unwrap(), before);
+    let repaired = std::fs::read_to_string(memory.join("3zzzzzzzzzzz2.json")).unwrap();
+    assert!(repaired.contains("bad \u{FFFD}"));
+
+    // only the unrecoverable U+FFFD record is still reported
+    let out = run(&dir, &["repair", "encoding", "--dry-run"]);
+    assert!(out.starts_with("at://self/ai.syui.gpt.memory/3zzzzzzzzzzz2  replacement"), "{}", out);
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use aigpt::core::config;
+use aigpt::{read_memory_all, save_memory, MCPServer, Provenance};
+use common::TestDir;
+use serde_json::{json, Value};
+
+/// Embedded use: one process-wide data dir, server over in-memory IO
+#[test]
+fn embedded_store_and_server() {
+    let dir = TestDir::new();
+    std::env::set_var("XDG_CONFIG_HOME", dir.config_home());
+    std::env::remove_var("AIGPT_DATA_DIR");
+    config::set_data_dir(dir.data_dir());
+
+    save_memory("prefers short answers", &Provenance::cli()).unwrap();
+    assert_eq!(read_memory_all().unwrap().len(), 1);
+
+    let input = [
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+        json!({
+            "jsonrpc": "2.0",
+            "id": 2,
+            "method": "tools/call",
+            "params": { "name": "count_memories", "arguments": {} }
+        }),
+    ]
+    .iter()
+    .map(|m| m.to_string() + "\n")
+    .collect::<String>();
+    let mut output = Vec::new();
+    MCPServer::new().serve(input.as_bytes(), &mut output).unwrap();
+
+    let responses: Vec<Value> = String::from_utf8(output)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0]["result"]["serverInfo"]["name"], "aigpt");
+    let text = responses[1]["result"]["content"][0]["text"].as_str().unwrap();
+    assert_eq!(serde_json::from_str::<Value>(text).unwrap(), json!({ "count": 1 }));
+}
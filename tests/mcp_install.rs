@@ -0,0 +1,157 @@
+mod common;
+
+use aigpt::mcp::install::{self, Change, Client, Health};
+use common::TestDir;
+use serde_json::{json, Value};
+use std::path::Path;
+
+fn read(path: &Path) -> Value {
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+#[test]
+fn claude_code_keeps_other_keys_and_backs_up() {
+    let dir = TestDir::new();
+    let path = dir.path.join(".claude.json");
+    std::fs::write(
+        &path,
+        r#"{"numStartups": 3, "mcpServers": {"other": {"command": "x"}}}"#,
+    )
+    .unwrap();
+
+    let exe = Path::new("/usr/local/bin/aigpt");
+    let entry = Client::ClaudeCode.entry(exe);
+    assert_eq!(install::install(&path, entry.clone()).unwrap(), Change::Added);
+
+    let doc = read(&path);
+    assert_eq!(doc["numStartups"], 3);
+    assert_eq!(doc["mcpServers"]["other"]["command"], "x");
+    assert_eq!(
+        doc["mcpServers"]["aigpt"],
+        json!({ "type": "stdio", "command": "/usr/local/bin/aigpt", "args": ["server"] })
+    );
+    let bak = read(&install::backup_path(&path));
+    assert!(bak["mcpServers"].get("aigpt").is_none());
+
+    assert_eq!(install::install(&path, entry).unwrap(), Change::Unchanged);
+    assert_eq!(install::doctor(&path, exe).unwrap(), Health::Ok);
+}
+
+#[test]
+fn install_and_uninstall_keep_key_order() {
+    let dir = TestDir::new();
+    let path = dir.path.join(".claude.json");
+    std::fs::write(&path, r#"{"zeta":1,"mcpServers":{},"alpha":2}"#).unwrap();
+    let keys = |path: &Path| -> Vec<String> {
+        read(path).as_object().unwrap().keys().cloned().collect()
+    };
+
+    let entry = Client::ClaudeCode.entry(Path::new("/usr/local/bin/aigpt"));
+    install::install(&path, entry).unwrap();
+    assert_eq!(keys(&path), ["zeta", "mcpServers", "alpha"]);
+    assert!(install::uninstall(&path).unwrap());
+    assert_eq!(keys(&path), ["zeta", "mcpServers", "alpha"]);
+    assert!(!dir.path.join(".claude.json.tmp").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn install_keeps_file_mode() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = TestDir::new();
+    let path = dir.path.join(".claude.json");
+    std::fs::write(&path, r#"{"mcpServers":{}}"#).unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+    let entry = Client::ClaudeCode.entry(Path::new("/usr/local/bin/aigpt"));
+    install::install(&path, entry).unwrap();
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    assert!(install::uninstall(&path).unwrap());
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
+
+#[cfg(unix)]
+#[test]
+fn install_writes_through_symlink() {
+    let dir = TestDir::new();
+    let target = dir.path.join("dotfiles").join("claude.json");
+    std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+    std::fs::write(&target, r#"{"numStartups":3}"#).unwrap();
+    let link = dir.path.join(".claude.json");
+    std::os::unix::fs::symlink(&target, &link).unwrap();
+
+    let entry = Client::ClaudeCode.entry(Path::new("/usr/local/bin/aigpt"));
+    assert_eq!(install::install(&link, entry).unwrap(), Change::Added);
+    assert!(std::fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+    let doc = read(&target);
+    assert_eq!(doc["numStartups"], 3);
+    assert!(doc["mcpServers"]["aigpt"].is_object());
+    assert_eq!(install::doctor(&link, Path::new("/usr/local/bin/aigpt")).unwrap(), Health::Ok);
+}
+
+#[test]
+fn claude_desktop_created_then_updated() {
+    let dir = TestDir::new();
+    let path = dir.path.join("Claude").join("claude_desktop_config.json");
+
+    let old = Path::new("/old/aigpt");
+    let new = Path::new("/new/aigpt");
+    install::install(&path, Client::ClaudeDesktop.entry(old)).unwrap();
+    assert_eq!(
+        read(&path)["mcpServers"]["aigpt"],
+        json!({ "command": "/old/aigpt", "args": ["server"] })
+    );
+    assert_eq!(
+        install::doctor(&path, new).unwrap(),
+        Health::Mismatch { command: "/old/aigpt".into() }
+    );
+
+    let change = install::install(&path, Client::ClaudeDesktop.entry(new)).unwrap();
+    assert!(matches!(change, Change::Updated { ref previous } if previous["command"] == "/old/aigpt"));
+    assert_eq!(install::doctor(&path, new).unwrap(), Health::Ok);
+}
+
+#[test]
+fn cursor_uninstall() {
+    let dir = TestDir::new();
+    let path = dir.path.join(".cursor").join("mcp.json");
+    assert!(!install::uninstall(&path).unwrap());
+
+    install::install(&path, Client::Cursor.entry(Path::new("/bin/aigpt"))).unwrap();
+    assert!(install::uninstall(&path).unwrap());
+    assert_eq!(read(&path), json!({ "mcpServers": {} }));
+    assert_eq!(install::doctor(&path, Path::new("/bin/aigpt")).unwrap(), Health::Missing);
+}
+
+#[test]
+fn refuses_json_with_comments() {
+    let dir = TestDir::new();
+    let path = dir.path.join("mcp.json");
+    let original = "{\n  // my servers\n  \"mcpServers\": {}\n}\n";
+    std::fs::write(&path, original).unwrap();
+
+    let err = install::install(&path, Client::Cursor.entry(Path::new("/bin/aigpt"))).unwrap_err();
+    assert!(err.to_string().contains("edit it by hand"));
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+    assert!(!install::backup_path(&path).exists());
+}
+
+#[test]
+fn cli_install_and_doctor() {
+    let dir = TestDir::new();
+    let path = dir.path.join("claude.json");
+    let path_arg = path.to_str().unwrap();
+
+    let out = dir.command().args(["mcp", "doctor", "--config", path_arg]).output().unwrap();
+    assert!(!out.status.success());
+
+    let out = dir.command().args(["mcp", "install", "--config", path_arg]).output().unwrap();
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).starts_with("ok added aigpt"));
+
+    let out = dir.command().args(["mcp", "doctor", "--config", path_arg]).output().unwrap();
+    assert!(out.status.success());
+}
@@ -0,0 +1,627 @@
+mod common;
+
+use common::{McpClient, TestDir};
+use serde_json::json;
+
+#[test]
+fn handshake_and_tools_list() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+
+    let init = client.handshake();
+    assert_eq!(init["serverInfo"]["name"], "aigpt");
+    assert!(init["protocolVersion"].is_string());
+    assert!(init["capabilities"]["tools"].is_object());
+
+    let tools = client.request("tools/list", json!({}));
+    let names: Vec<&str> = tools["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "read_core",
+            "read_memory",
+            "save_memory",
+            "compress",
+            "count_memories",
+            "memory_exists",
+            "get_server_info",
+            "estimate_tokens"
+        ]
+    );
+
+    assert!(client.finish().success());
+}
+
+#[test]
+fn memory_lifecycle() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+
+    let core = client.call_tool("read_core", json!({}));
+    assert_eq!(core["value"]["$type"], "ai.syui.gpt.core");
+
+    // create
+    let saved = client.call_tool("save_memory", json!({ "content": "likes rust" }));
+    assert_eq!(saved, json!({ "success": true, "count": 1 }));
+    client.call_tool("save_memory", json!({ "content": "lives in tokyo" }));
+
+    // read back
+    let read = client.call_tool("read_memory", json!({}));
+    assert_eq!(read["count"], 2);
+    let texts: Vec<&str> = read["records"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| r["value"]["content"]["text"].as_str().unwrap())
+        .collect();
+    assert_eq!(texts, ["likes rust", "lives in tokyo"]);
+    assert!(read["records"][0]["uri"]
+        .as_str()
+        .unwrap()
+        .starts_with("at://self/ai.syui.gpt.memory/"));
+
+    // replace
+    let compressed = client.call_tool("compress", json!({ "items": ["likes rust, lives in tokyo"] }));
+    assert_eq!(compressed, json!({ "success": true, "count": 1 }));
+    let read = client.call_tool("read_memory", json!({}));
+    assert_eq!(read["count"], 1);
+    assert_eq!(read["records"][0]["value"]["content"]["text"], "likes rust, lives in tokyo");
+
+    // delete all
+    client.call_tool("compress", json!({ "items": [] }));
+    let read = client.call_tool("read_memory", json!({}));
+    assert_eq!(read["count"], 0);
+    assert!(dir.memory_files().is_empty());
+
+    assert!(client.finish().success());
+}
+
+#[test]
+fn invalid_arguments_write_nothing() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+
+    let cases = [
+        ("save_memory", json!({})),
+        ("save_memory", json!({ "content": "   " })),
+        ("save_memory", json!({ "content": 42 })),
+        ("compress", json!({ "items": "not an array" })),
+        ("compress", json!({ "items": ["ok", ""] })),
+    ];
+    for (tool, args) in cases {
+        let result = client.call_tool(tool, args.clone());
+        let error = result["error"].as_str().unwrap_or_default();
+        assert!(error.starts_with("VALIDATION:"), "{} {} -> {}", tool, args, result);
+    }
+    assert!(dir.memory_files().is_empty());
+}
+
+#[test]
+fn unknown_method_and_tool() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+
+    let response = client.request("no/such/method", json!({}));
+    assert_eq!(response["error"]["code"], -32601);
+
+    let result = client.call_tool("no_such_tool", json!({}));
+    assert_eq!(result["error"], "Unknown tool: no_such_tool");
+}
+
+#[test]
+fn malformed_line_does_not_kill_the_loop() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+
+    client.send_raw("{not json");
+    let response = client.read_response();
+    assert_eq!(response["error"]["code"], -32700);
+    assert!(response["id"].is_null());
+
+    client.send_raw("");
+    let read = client.call_tool("read_memory", json!({}));
+    assert_eq!(read["count"], 0);
+
+    for line in ["5", "\"ping\"", "null", "[]"] {
+        client.send_raw(line);
+        let response = client.read_response();
+        assert_eq!(response["error"]["code"], -32600, "{}", line);
+        assert!(response["id"].is_null());
+    }
+
+    // batches are answered element by element; notifications get nothing
+    let batch = json!([
+        { "jsonrpc": "2.0", "id": 90, "method": "ping" },
+        7,
+        { "jsonrpc": "2.0", "method": "notifications/initialized" },
+        { "jsonrpc": "2.0", "id": 91, "method": "no/such" }
+    ]);
+    client.send_raw(&batch.to_string());
+    let batch = client.read_response();
+    let batch = batch.as_array().unwrap();
+    assert_eq!(batch.len(), 3);
+    assert_eq!(batch[0], json!({ "jsonrpc": "2.0", "id": 90, "result": {} }));
+    assert_eq!(batch[1]["error"]["code"], -32600);
+    assert_eq!(batch[2]["id"], 91);
+    assert_eq!(batch[2]["error"]["code"], -32601);
+
+    client.send_raw(r#"[{"jsonrpc":"2.0","method":"notifications/initialized"}]"#);
+    let read = client.call_tool("read_memory", json!({}));
+    assert_eq!(read["count"], 0);
+
+    assert!(client.finish().success());
+}
+
+#[test]
+fn initialize_instructions_default_and_override() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    let init = client.handshake();
+    let instructions = init["instructions"].as_str().unwrap();
+    assert!(instructions.contains("save_memory"));
+    assert!(instructions.contains("0/100 records"));
+    assert_eq!(init["capabilities"]["tools"]["listChanged"], false);
+    client.finish();
+
+    let file = dir.config_home().join("ai.syui.gpt").join("instructions.md");
+    std::fs::write(&file, "custom guide ({count} of {max})").unwrap();
+    let mut client = McpClient::start(&dir);
+    client.call_tool("save_memory", json!({ "content": "remember me" }));
+    let init = client.handshake();
+    let instructions = init["instructions"].as_str().unwrap();
+    assert!(instructions.starts_with("custom guide (1 of 100)\n\nMemory records follow"));
+    assert!(instructions.ends_with("<memory>\nremember me\n</memory>"));
+    client.finish();
+
+    std::fs::write(&file, "").unwrap();
+    let mut client = McpClient::start(&dir);
+    let init = client.handshake();
+    assert!(init["instructions"].as_str().unwrap().starts_with("Memory records follow"));
+    client.finish();
+}
+
+#[test]
+fn estimate_tokens_tool() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+
+    let result = client.call_tool("estimate_tokens", json!({ "text": "今日は晴れ" }));
+    assert_eq!(result, json!({ "tokens": 5, "chars": 5 }));
+    let result = client.call_tool("estimate_tokens", json!({}));
+    assert_eq!(result["error"], "VALIDATION: text: required");
+}
+
+#[test]
+fn read_memory_order_and_paging() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+    for text in ["one", "two", "three"] {
+        client.call_tool("save_memory", json!({ "content": text }));
+    }
+    let texts = |result: &serde_json::Value| -> Vec<String> {
+        result["records"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["value"]["content"]["text"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let oldest = client.call_tool("read_memory", json!({ "order": "oldest", "limit": 2 }));
+    assert_eq!(texts(&oldest), ["one", "two"]);
+    assert_eq!(oldest["total"], 3);
+
+    let newest = client.call_tool("read_memory", json!({ "order": "newest", "limit": 2 }));
+    assert_eq!(texts(&newest), ["three", "two"]);
+
+    let page = client.call_tool("read_memory", json!({ "order": "newest", "offset": 2 }));
+    assert_eq!(texts(&page), ["one"]);
+    assert_eq!(page["count"], 1);
+
+    let future = client.call_tool("read_memory", json!({ "since": "2999-01-01" }));
+    assert_eq!(future["total"], 0);
+    let past = client.call_tool("read_memory", json!({ "since": "2000-01-01T09:00:00+09:00" }));
+    assert_eq!(past["total"], 3);
+
+    for args in [
+        json!({ "order": "random" }),
+        json!({ "limit": 0 }),
+        json!({ "offset": -1 }),
+        json!({ "since": "yesterday" }),
+    ] {
+        let result = client.call_tool("read_memory", args.clone());
+        assert!(result["error"].as_str().unwrap().starts_with("VALIDATION:"), "{}", args);
+    }
+}
+
+#[test]
+fn safe_mode_hides_and_refuses_compress() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+    client.call_tool("save_memory", json!({ "content": "keep" }));
+    client.finish();
+
+    for mut client in [
+        McpClient::start_with(&dir, &["--safe-mode"]),
+        {
+            dir.write_config(json!({ "safe_mode": true }));
+            McpClient::start(&dir)
+        },
+    ] {
+        client.handshake();
+        let tools = client.request("tools/list", json!({}));
+        let names: Vec<&str> = tools["result"]["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["name"].as_str().unwrap())
+            .collect();
+        assert!(!names.contains(&"compress"));
+        assert!(names.contains(&"save_memory"));
+
+        let result = client.call_tool("compress", json!({ "items": [] }));
+        assert_eq!(result["error"], "Tool disabled in safe mode: compress");
+        assert_eq!(dir.memory_files().len(), 1);
+        client.finish();
+    }
+}
+
+#[test]
+fn records_carry_provenance() {
+    let dir = TestDir::new();
+    let out = dir.command().args(["save-memory", "from the cli"]).output().unwrap();
+    assert!(out.status.success());
+
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+    client.call_tool("save_memory", json!({ "content": "from mcp" }));
+
+    let read = client.call_tool("read_memory", json!({}));
+    let cli = &read["records"][0]["value"];
+    assert_eq!(cli["createdBy"], "cli");
+    assert!(cli.get("client").is_none());
+    let mcp = &read["records"][1]["value"];
+    assert_eq!(mcp["createdBy"], "mcp");
+    assert_eq!(mcp["client"], json!({ "name": "aigpt-test", "version": "0.0.0" }));
+    client.finish();
+
+    let out = dir.command().args(["read-memory", "--created-by", "mcp"]).output().unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("from mcp"));
+    assert!(!stdout.contains("from the cli"));
+}
+
+#[test]
+fn session_state_survives_restart() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+    client.call_tool("save_memory", json!({ "content": "a" }));
+    let info = client.call_tool("get_server_info", json!({}));
+    assert_eq!(info["restarts"], 0);
+    // counters are updated after the call, so this one is not included
+    assert_eq!(info["state"]["tool_calls"], 1);
+    client.finish();
+
+    let mut client = McpClient::start(&dir);
+    let info = client.call_tool("get_server_info", json!({}));
+    assert_eq!(info["restarts"], 1);
+    assert_eq!(info["state"]["starts"], 2);
+    assert_eq!(info["state"]["tool_calls"], 2);
+    assert_eq!(info["state"]["last_tool"], "get_server_info");
+    assert_eq!(info["state"]["last_client"]["name"], "aigpt-test");
+    assert!(info["uptime_secs"].is_u64());
+    client.finish();
+
+    assert!(dir.data_dir().join("server.json").exists());
+}
+
+#[test]
+fn compress_conflicts_when_records_changed() {
+    let dir = TestDir::new();
+    let mut a = McpClient::start(&dir);
+    let mut b = McpClient::start(&dir);
+    a.handshake();
+    b.handshake();
+
+    a.call_tool("save_memory", json!({ "content": "one" }));
+    let read = a.call_tool("read_memory", json!({}));
+    let revision = read["revision"].as_str().unwrap().to_string();
+
+    b.call_tool("save_memory", json!({ "content": "two" }));
+
+    let result = a.call_tool(
+        "compress",
+        json!({ "items": ["one (compressed)"], "expected_revision": revision }),
+    );
+    assert!(result["error"].as_str().unwrap().starts_with("CONFLICT:"));
+    assert_eq!(result["records"].as_array().unwrap().len(), 2);
+    assert_eq!(dir.memory_files().len(), 2);
+
+    let current = result["revision"].as_str().unwrap();
+    assert_ne!(current, revision);
+    let result = a.call_tool(
+        "compress",
+        json!({ "items": ["one and two"], "expected_revision": current }),
+    );
+    assert_eq!(result, json!({ "success": true, "count": 1 }));
+    assert_eq!(dir.memory_files().len(), 1);
+}
+
+#[test]
+fn count_matches_read_for_same_filters() {
+    let dir = TestDir::new();
+    dir.command().args(["save-memory", "Rust from the cli"]).output().unwrap();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+    for text in ["likes rust", "lives in tokyo", "writes RUST daily"] {
+        client.call_tool("save_memory", json!({ "content": text }));
+    }
+
+    for filter in [
+        json!({}),
+        json!({ "query": "rust" }),
+        json!({ "query": "rust", "created_by": "mcp" }),
+        json!({ "created_by": "cli" }),
+        json!({ "since": "1d" }),
+        json!({ "since": "2999-01-01" }),
+    ] {
+        let count = client.call_tool("count_memories", filter.clone());
+        let read = client.call_tool("read_memory", filter.clone());
+        assert_eq!(count["count"], read["total"], "{}", filter);
+    }
+    assert_eq!(client.call_tool("count_memories", json!({ "query": "rust" }))["count"], 3);
+
+    let found = client.call_tool("memory_exists", json!({ "content": " lives in tokyo " }));
+    assert_eq!(found["exists"], true);
+    assert!(found["uri"].as_str().unwrap().starts_with("at://"));
+    let missing = client.call_tool("memory_exists", json!({ "content": "lives in" }));
+    assert_eq!(missing, json!({ "exists": false }));
+    client.finish();
+
+    let out = dir.command().args(["count", "--query", "rust", "--since", "30d"]).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "3");
+    let out = dir.command().args(["count", "--since", "someday"]).output().unwrap();
+    assert!(!out.status.success());
+}
+
+#[test]
+fn out_of_range_since_is_a_validation_error() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+    client.call_tool("save_memory", json!({ "content": "likes rust" }));
+
+    for since in ["99999999999999d", "999999999999d", "-5d"] {
+        for tool in ["read_memory", "count_memories"] {
+            let result = client.call_tool(tool, json!({ "since": since }));
+            let error = result["error"].as_str().unwrap_or_default();
+            assert!(error.starts_with("VALIDATION"), "{} {}: {}", tool, since, result);
+        }
+    }
+    assert_eq!(client.call_tool("count_memories", json!({}))["count"], 1);
+    client.finish();
+
+    let out = dir.command().args(["count", "--since", "999999999999d"]).output().unwrap();
+    assert_eq!(out.status.code(), Some(1));
+    let out = dir.command().args(["count", "--since=-5d"]).output().unwrap();
+    assert_eq!(out.status.code(), Some(1));
+}
+
+#[test]
+fn structured_content_follows_negotiated_protocol() {
+    let dir = TestDir::new();
+
+    // 2024-11-05: text content only
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+    let tools = client.request("tools/list", json!({}));
+    let tools = tools["result"]["tools"].as_array().unwrap();
+    assert!(tools.iter().all(|t| t.get("outputSchema").is_none()));
+    let count = json!({ "name": "count_memories", "arguments": {} });
+    let response = client.request("tools/call", count.clone());
+    assert!(response["result"].get("structuredContent").is_none());
+    assert!(client.finish().success());
+
+    // 2025-06-18: structuredContent mirrors the text block
+    let mut client = McpClient::start(&dir);
+    let init = client.request(
+        "initialize",
+        json!({
+            "protocolVersion": "2025-06-18",
+            "capabilities": {},
+            "clientInfo": { "name": "aigpt-test", "version": "0.0.0" }
+        }),
+    );
+    assert_eq!(init["result"]["protocolVersion"], "2025-06-18");
+    let tools = client.request("tools/list", json!({}));
+    let count_tool = tools["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["name"] == "count_memories")
+        .unwrap()
+        .clone();
+    assert_eq!(count_tool["outputSchema"]["required"], json!(["count"]));
+
+    let response = client.request("tools/call", count);
+    let text: serde_json::Value =
+        serde_json::from_str(response["result"]["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(response["result"]["structuredContent"], text);
+
+    // errors are flagged instead of structured
+    let blank = json!({ "name": "save_memory", "arguments": { "content": " " } });
+    let response = client.request("tools/call", blank);
+    assert_eq!(response["result"]["isError"], true);
+    assert!(response["result"].get("structuredContent").is_none());
+    assert!(client.finish().success());
+}
+
+#[test]
+fn unknown_protocol_version_gets_newest() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    let init = client.request(
+        "initialize",
+        json!({
+            "protocolVersion": "1999-01-01",
+            "capabilities": {},
+            "clientInfo": { "name": "aigpt-test", "version": "0.0.0" }
+        }),
+    );
+    assert_eq!(init["result"]["protocolVersion"], "2025-06-18");
+    assert!(client.finish().success());
+}
+
+#[test]
+fn repeated_save_is_deduplicated() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+
+    let first = client.call_tool("save_memory", json!({ "content": "likes rust" }));
+    assert_eq!(first, json!({ "success": true, "count": 1 }));
+    let repeat = client.call_tool("save_memory", json!({ "content": "likes rust " }));
+    assert_eq!(repeat, json!({ "success": true, "count": 1, "deduplicated": true }));
+    assert_eq!(dir.memory_files().len(), 1);
+
+    let forced = client.call_tool(
+        "save_memory",
+        json!({ "content": "likes rust", "allow_duplicate": true }),
+    );
+    assert_eq!(forced, json!({ "success": true, "count": 2 }));
+
+    let info = client.call_tool("get_server_info", json!({}));
+    assert_eq!(info["state"]["deduplicated"], 1);
+    assert!(client.finish().success());
+
+    // bot.dedupe_window = 0 turns it off
+    dir.write_config(json!({ "dedupe_window": 0 }));
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+    let again = client.call_tool("save_memory", json!({ "content": "likes go" }));
+    assert!(again.get("deduplicated").is_none());
+    let again = client.call_tool("save_memory", json!({ "content": "likes go" }));
+    assert!(again.get("deduplicated").is_none());
+    assert_eq!(dir.memory_files().len(), 4);
+    assert!(client.finish().success());
+}
+
+#[cfg(unix)]
+#[test]
+fn sigterm_mid_request_leaves_readable_records() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+    for i in 0..3 {
+        client.call_tool("save_memory", json!({ "content": format!("kept {}", i) }));
+    }
+
+    // queue writes without waiting for their responses, then kill
+    for i in 0..50 {
+        let msg = json!({
+            "jsonrpc": "2.0",
+            "id": 100 + i,
+            "method": "tools/call",
+            "params": { "name": "save_memory", "arguments": { "content": format!("queued {}", i) } }
+        });
+        client.send_raw(&msg.to_string());
+    }
+    client.terminate();
+
+    let texts: Vec<String> = dir
+        .memory_files()
+        .iter()
+        .map(|path| {
+            let record: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(path).unwrap())
+                    .unwrap_or_else(|e| panic!("{} is not valid JSON: {}", path.display(), e));
+            record["value"]["content"]["text"].as_str().unwrap().to_string()
+        })
+        .collect();
+    for i in 0..3 {
+        assert!(texts.contains(&format!("kept {}", i)));
+    }
+}
+
+#[test]
+fn ping_shutdown_and_garbage_initialize() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+
+    // params of the wrong shape still get a usable initialize
+    let init = client.request("initialize", json!("garbage"));
+    assert_eq!(init["result"]["protocolVersion"], "2025-06-18");
+    assert_eq!(init["result"]["serverInfo"]["name"], "aigpt");
+    client.notify("notifications/initialized", json!({}));
+
+    let pong = client.request("ping", json!({}));
+    assert_eq!(pong["result"], json!({}));
+
+    // shutdown is answered, then the server exits without waiting for EOF
+    let bye = client.request("shutdown", json!({}));
+    assert_eq!(bye["result"], json!({}));
+    assert!(client.wait().success());
+
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+    client.notify("exit", json!({}));
+    assert!(client.wait().success());
+}
+
+#[test]
+fn save_after_compress_is_not_deduplicated() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+
+    client.call_tool("save_memory", json!({ "content": "likes rust" }));
+    client.call_tool("compress", json!({ "items": ["other"] }));
+    let again = client.call_tool("save_memory", json!({ "content": "likes rust" }));
+    assert_eq!(again, json!({ "success": true, "count": 2 }));
+
+    let exists = client.call_tool("memory_exists", json!({ "content": "likes rust" }));
+    assert_eq!(exists["exists"], true);
+    assert!(client.finish().success());
+}
+
+#[test]
+fn save_after_replace_is_not_deduplicated() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+
+    client.call_tool("save_memory", json!({ "content": "likes rust" }));
+    let args = ["replace", "--find", "rust", "--replace", "go", "--yes"];
+    assert!(dir.command().args(args).status().unwrap().success());
+    let again = client.call_tool("save_memory", json!({ "content": "likes rust" }));
+    assert_eq!(again, json!({ "success": true, "count": 2 }));
+    assert!(client.finish().success());
+}
+
+#[test]
+fn alternating_saves_are_deduplicated() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+
+    for content in ["likes rust", "lives in tokyo", "likes rust", "lives in tokyo"] {
+        client.call_tool("save_memory", json!({ "content": content }));
+    }
+    assert_eq!(dir.memory_files().len(), 2);
+    let again = client.call_tool("save_memory", json!({ "content": "likes rust" }));
+    assert_eq!(again["deduplicated"], true);
+    assert!(client.finish().success());
+}
@@ -0,0 +1,104 @@
+mod common;
+
+use common::{McpClient, TestDir};
+use serde_json::json;
+
+#[test]
+fn cli_reads_but_refuses_writes() {
+    let dir = TestDir::new();
+    assert!(dir.command().args(["save", "kept"]).status().unwrap().success());
+    let files = dir.memory_files();
+
+    let out = dir.command().args(["--read-only", "save", "refused"]).output().unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("read-only mode"));
+    assert_eq!(dir.memory_files(), files);
+
+    let out = dir.command().args(["--read-only", "ls"]).output().unwrap();
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("kept"));
+
+    let out = dir
+        .command()
+        .args(["--read-only", "replace", "--find", "kept", "--replace", "x", "--yes"])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+}
+
+#[test]
+fn fresh_dir_stays_untouched() {
+    let dir = TestDir::new();
+    let out = dir.command().args(["--read-only", "count"]).output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "0\n");
+    assert!(!dir.data_dir().exists());
+    assert!(!dir.config_home().exists());
+}
+
+#[test]
+fn server_hides_write_tools() {
+    let dir = TestDir::new();
+    assert!(dir.command().args(["save", "kept"]).status().unwrap().success());
+
+    let mut client = McpClient::start_with(&dir, &["--read-only"]);
+    client.handshake();
+    let tools = client.request("tools/list", json!({}));
+    let names: Vec<&str> = tools["result"]["tools"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap())
+        .collect();
+    assert!(names.contains(&"read_memory"));
+    assert!(!names.contains(&"save_memory"));
+    assert!(!names.contains(&"compress"));
+
+    let result = client.call_tool("save_memory", json!({ "content": "refused" }));
+    assert_eq!(result["error"], "Tool disabled in read-only mode: save_memory");
+    let read = client.call_tool("read_memory", json!({}));
+    assert_eq!(read["count"], 1);
+    assert!(client.finish().success());
+    assert!(!dir.data_dir().join("server.json").exists());
+}
+
+#[test]
+fn setup_and_mcp_config_changes_are_refused() {
+    let dir = TestDir::new();
+    let config = dir.path.join("claude.json");
+    let config_arg = config.to_str().unwrap();
+    let error = "read-only mode (--read-only): memory records cannot be changed";
+
+    let out = dir.command().args(["--read-only", "setup"]).output().unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains(error));
+    assert!(!dir.config_home().exists());
+
+    let out = dir
+        .command()
+        .args(["--read-only", "mcp", "install", "--config", config_arg])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains(error));
+    assert!(!config.exists());
+
+    let out = dir.command().args(["mcp", "install", "--config", config_arg]).output().unwrap();
+    assert!(out.status.success());
+    let installed = std::fs::read_to_string(&config).unwrap();
+    let out = dir
+        .command()
+        .args(["--read-only", "mcp", "uninstall", "--config", config_arg])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains(error));
+    assert_eq!(std::fs::read_to_string(&config).unwrap(), installed);
+
+    let out = dir
+        .command()
+        .args(["--read-only", "mcp", "doctor", "--config", config_arg])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+}
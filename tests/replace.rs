@@ -0,0 +1,119 @@
+mod common;
+
+use common::{run, texts, TestDir};
+
+fn fixture() -> TestDir {
+    let dir = TestDir::new();
+    run(&dir, &["save", "working on oldname today"]);
+    run(&dir, &["save", "oldname ships oldname-cli"]);
+    run(&dir, &["save", "unrelated"]);
+    dir
+}
+
+#[test]
+fn preview_and_dry_run_write_nothing() {
+    let dir = fixture();
+    let before: Vec<_> = dir.memory_files().iter().map(|p| std::fs::read(p).unwrap()).collect();
+
+    let out = run(&dir, &["replace", "--find", "oldname", "--replace", "newname"]);
+    assert!(out.starts_with("2 records, 3 occurrences\n"), "{}", out);
+    assert!(out.contains("working on {-oldname-}{+newname+} today"), "{}", out);
+    assert!(out.ends_with("Run again with --yes to apply.\n"), "{}", out);
+
+    let args = ["replace", "--find", "oldname", "--replace", "newname", "--dry-run", "--yes"];
+    let out = run(&dir, &args);
+    assert!(!out.contains("Updated"), "{}", out);
+
+    let after: Vec<_> = dir.memory_files().iter().map(|p| std::fs::read(p).unwrap()).collect();
+    assert_eq!(before, after);
+}
+
+#[test]
+fn literal_replace_applies_with_yes() {
+    let dir = fixture();
+    let out = run(&dir, &["replace", "--find", "oldname", "--replace", "newname", "--yes"]);
+    assert!(out.ends_with("Updated 2 records.\n"), "{}", out);
+    assert_eq!(
+        texts(&dir),
+        ["working on newname today", "newname ships newname-cli", "unrelated"]
+    );
+
+    let record: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&dir.memory_files()[0]).unwrap()).unwrap();
+    assert!(record["value"]["updatedAt"].is_string());
+
+    // regex metacharacters are taken literally
+    let out = run(&dir, &["replace", "--find", "new.*", "--replace", "x", "--yes"]);
+    assert_eq!(out, "No memory records contain 'new.*'\n");
+}
+
+#[test]
+fn regex_replace_expands_capture_groups() {
+    let dir = fixture();
+    let args = ["replace", "--regex", "--find", r"old(\w+)", "--replace", "new${1}", "--yes"];
+    let out = run(&dir, &args);
+    assert!(out.starts_with("2 records, 3 occurrences\n"), "{}", out);
+    assert!(out.contains("working on {-oldname-}{+newname+} today"), "{}", out);
+    assert_eq!(
+        texts(&dir),
+        ["working on newname today", "newname ships newname-cli", "unrelated"]
+    );
+
+    let args = ["replace", "--regex", "--find", r"(\w+) ships (\w+)", "--replace", "$2 by $1"];
+    let out = run(&dir, &[&args[..], &["--yes"]].concat());
+    assert!(out.contains("{-newname ships newname-}{+newname by newname+}"), "{}", out);
+    assert_eq!(texts(&dir)[1], "newname by newname-cli");
+
+    let out = run(&dir, &["replace", "--regex", "--find", "^zzz", "--replace", "x"]);
+    assert_eq!(out, "No memory records match '^zzz'\n");
+
+    let out = dir
+        .command()
+        .args(["replace", "--regex", "--find", "(unclosed", "--replace", "x", "--yes"])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Invalid regex '(unclosed'"));
+}
+
+#[test]
+fn unchanged_text_is_not_rewritten() {
+    let dir = fixture();
+    let before: Vec<_> = dir.memory_files().iter().map(|p| std::fs::read(p).unwrap()).collect();
+
+    let out = run(&dir, &["replace", "--find", "oldname", "--replace", "oldname", "--yes"]);
+    assert!(out.ends_with("Updated 0 records.\n"), "{}", out);
+    let args = ["replace", "--regex", "--find", "(old)name", "--replace", "${1}name", "--yes"];
+    let out = run(&dir, &args);
+    assert!(out.ends_with("Updated 0 records.\n"), "{}", out);
+
+    let after: Vec<_> = dir.memory_files().iter().map(|p| std::fs::read(p).unwrap()).collect();
+    assert_eq!(before, after);
+    let tmp = std::fs::read_dir(dir.memory_dir()).unwrap().flatten();
+    assert!(tmp.filter(|e| e.file_name().to_string_lossy().ends_with(".tmp")).count() == 0);
+}
+
+#[test]
+fn filters_limit_the_replace() {
+    let dir = fixture();
+    let args = ["replace", "--find", "oldname", "--replace", "newname", "--query", "today", "--yes"];
+    run(&dir, &args);
+    assert_eq!(
+        texts(&dir),
+        ["working on newname today", "oldname ships oldname-cli", "unrelated"]
+    );
+}
+
+#[test]
+fn over_limit_result_writes_nothing() {
+    let dir = fixture();
+    dir.write_config(serde_json::json!({ "content_max": 30 }));
+    let out = dir
+        .command()
+        .args(["replace", "--find", "oldname", "--replace", "a-much-longer-name", "--yes"])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("bot.content_max"));
+    assert_eq!(texts(&dir)[0], "working on oldname today");
+}
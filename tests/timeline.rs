@@ -0,0 +1,103 @@
+mod common;
+
+use common::{run, TestDir};
+use serde_json::{json, Value};
+
+/// Write a record with a fixed createdAt, bypassing save-memory
+fn write_record(dir: &TestDir, tid: &str, created_at: &str, text: &str) {
+    let memory = dir.memory_dir();
+    std::fs::create_dir_all(&memory).unwrap();
+    let record = json!({
+        "uri": format!("at://self/ai.syui.gpt.memory/{}", tid),
+        "value": {
+            "$type": "ai.syui.gpt.memory",
+            "did": "self",
+            "content": { "$type": "ai.syui.gpt.memory#markdown", "text": text },
+            "createdAt": created_at
+        }
+    });
+    std::fs::write(memory.join(format!("{}.json", tid)), record.to_string()).unwrap();
+}
+
+fn fixture() -> TestDir {
+    let dir = TestDir::new();
+    write_record(&dir, "3aaaaaaaaaaa2", "2026-01-05T09:14:00Z", "likes rust\nand go");
+    write_record(&dir, "3aaaaaaaaaaa3", "2026-01-05T18:02:30Z", "lives in tokyo");
+    write_record(&dir, "3aaaaaaaaaaa4", "2026-01-06T07:00:00Z", "drinks coffee");
+    write_record(&dir, "3aaaaaaaaaaa5", "2026-01-19T12:00:00Z", "started a new job");
+    dir
+}
+
+#[test]
+fn day_timeline_text() {
+    let dir = fixture();
+    assert_eq!(
+        run(&dir, &["timeline"]),
+        "\
+2026-01-05 (2)
+  09:14  3aaaaaaaaaaa2  likes rust
+  18:02  3aaaaaaaaaaa3  lives in tokyo
+2026-01-06 (1)
+  07:00  3aaaaaaaaaaa4  drinks coffee
+  — 12 days with no memories —
+2026-01-19 (1)
+  12:00  3aaaaaaaaaaa5  started a new job
+"
+    );
+}
+
+#[test]
+fn week_timeline_text() {
+    let dir = fixture();
+    assert_eq!(
+        run(&dir, &["timeline", "--group", "week", "--gap", "30"]),
+        "\
+2026-W02 (3)
+  01-05 09:14  3aaaaaaaaaaa2  likes rust
+  01-05 18:02  3aaaaaaaaaaa3  lives in tokyo
+  01-06 07:00  3aaaaaaaaaaa4  drinks coffee
+2026-W04 (1)
+  01-19 12:00  3aaaaaaaaaaa5  started a new job
+"
+    );
+}
+
+#[test]
+fn timeline_json_and_filters() {
+    let dir = fixture();
+    let out: Value =
+        serde_json::from_str(&run(&dir, &["timeline", "--format", "json", "--since", "2026-01-06"]))
+            .unwrap();
+    assert_eq!(
+        out,
+        json!([
+            {
+                "label": "2026-01-06",
+                "count": 1,
+                "records": [{ "tid": "3aaaaaaaaaaa4", "createdAt": "2026-01-06T07:00:00Z", "text": "drinks coffee" }]
+            },
+            {
+                "label": "2026-01-19",
+                "count": 1,
+                "gap_days": 12,
+                "records": [{ "tid": "3aaaaaaaaaaa5", "createdAt": "2026-01-19T12:00:00Z", "text": "started a new job" }]
+            }
+        ])
+    );
+
+    let out = run(&dir, &["timeline", "--query", "TOKYO"]);
+    assert_eq!(out, "2026-01-05 (1)\n  18:02  3aaaaaaaaaaa3  lives in tokyo\n");
+    assert_eq!(run(&dir, &["timeline", "--query", "nothing"]), "No memory records found\n");
+}
+
+#[test]
+fn gap_zero_and_negative() {
+    let dir = fixture();
+    let out = run(&dir, &["timeline", "--gap", "0"]);
+    assert_eq!(out.matches("days with no memories").count(), 1, "{}", out);
+    assert!(out.starts_with("2026-01-05 (2)\n"), "{}", out);
+
+    let out = dir.command().args(["timeline", "--gap=-1"]).output().unwrap();
+    assert!(!out.status.success());
+    assert!(out.stdout.is_empty());
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use aigpt::core::timing::Timings;
+use common::{McpClient, TestDir};
+use serde_json::json;
+use std::time::Duration;
+
+#[test]
+fn record_sums_repeats_in_first_seen_order() {
+    let mut t = Timings::new(true);
+    t.record("read", Duration::from_millis(2));
+    t.record("write", Duration::from_millis(5));
+    t.record("read", Duration::from_millis(3));
+    assert_eq!(t.total(), Duration::from_millis(10));
+
+    let report = t.report();
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines.len(), 4, "{}", report);
+    assert_eq!(lines[0], "timings:");
+    assert!(lines[1].starts_with("  read "), "{}", report);
+    assert!(lines[1].contains("5.000 ms  (x2)"), "{}", report);
+    assert!(lines[2].starts_with("  write "), "{}", report);
+    assert!(lines[2].ends_with("5.000 ms"), "{}", report);
+    assert!(lines[3].starts_with("  total "), "{}", report);
+    assert!(lines[3].ends_with("10.000 ms"), "{}", report);
+}
+
+#[test]
+fn disabled_timings_are_a_no_op() {
+    let mut t = Timings::new(false);
+    assert!(!t.enabled());
+    t.record("read", Duration::from_millis(2));
+    assert_eq!(t.time("write", || 42), 42);
+    assert_eq!(t.total(), Duration::ZERO);
+    assert_eq!(t.report().lines().count(), 2);
+}
+
+#[test]
+fn profile_flag_prints_to_stderr() {
+    let dir = TestDir::new();
+    let out = dir.command().args(["--profile", "count"]).output().unwrap();
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "0\n");
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.starts_with("timings:\n"), "{}", stderr);
+    assert!(stderr.contains("  init "), "{}", stderr);
+    assert!(stderr.contains("  total "), "{}", stderr);
+
+    let out = dir.command().arg("count").output().unwrap();
+    assert!(!String::from_utf8_lossy(&out.stderr).contains("timings:"));
+}
+
+#[test]
+fn server_profile_adds_duration_to_tool_results() {
+    let dir = TestDir::new();
+    let mut client = McpClient::start_with(&dir, &["--profile"]);
+    client.handshake();
+    let response = client.request(
+        "tools/call",
+        json!({ "name": "count_memories", "arguments": {} }),
+    );
+    assert!(response["result"]["meta"]["duration_ms"].as_f64().unwrap() >= 0.0);
+    assert!(client.finish().success());
+
+    let mut client = McpClient::start(&dir);
+    client.handshake();
+    let response = client.request(
+        "tools/call",
+        json!({ "name": "count_memories", "arguments": {} }),
+    );
+    assert!(response["result"].get("meta").is_none());
+    assert!(client.finish().success());
+}
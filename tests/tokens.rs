@@ -0,0 +1,44 @@
+use aigpt::core::tokens::estimate;
+
+/// Within `pct` percent of a reference cl100k count
+fn assert_close(text: &str, reference: usize, pct: usize) {
+    let got = estimate(text);
+    let diff = got.abs_diff(reference);
+    assert!(
+        diff * 100 <= reference * pct,
+        "{:?}: estimated {} vs reference {}",
+        text,
+        got,
+        reference
+    );
+}
+
+#[test]
+fn empty_text_is_zero() {
+    assert_eq!(estimate(""), 0);
+}
+
+#[test]
+fn english_close_to_reference() {
+    assert_close("The quick brown fox jumps over the lazy dog.", 10, 20);
+    assert_close(
+        "Memory records are stored as JSON files, one record per memory element, \
+         and the MCP server reads them on every request.",
+        25,
+        25,
+    );
+}
+
+#[test]
+fn cjk_counts_more_per_char_than_ascii() {
+    let ja = "今日は晴れています";
+    assert_eq!(estimate(ja), ja.chars().count());
+    assert!(estimate("こんにちは") > estimate("hello"));
+}
+
+#[test]
+fn partial_tokens_round_up() {
+    assert_eq!(estimate("a"), 1);
+    assert_eq!(estimate("abcd"), 1);
+    assert_eq!(estimate("abcde"), 2);
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use aigpt::core::usage::{self, Entry, Row};
+use common::TestDir;
+use serde_json::json;
+
+fn entry(at: &str, command: &str, ms: f64, ok: bool) -> Entry {
+    Entry { at: at.to_string(), command: command.to_string(), ms, ok }
+}
+
+#[test]
+fn report_aggregates_per_command() {
+    let entries = [
+        entry("2026-01-01T00:00:00Z", "count", 1.0, true),
+        entry("2026-01-02T00:00:00Z", "save-memory", 2.0, true),
+        entry("2026-01-03T00:00:00Z", "save-memory", 4.0, false),
+        entry("2026-01-04T00:00:00Z", "save-memory", 6.0, true),
+        entry("2026-01-05T00:00:00Z", "count", 3.0, true),
+    ];
+    let row = |command: &str, runs, failures, avg_ms| Row {
+        command: command.to_string(),
+        runs,
+        failures,
+        avg_ms,
+    };
+
+    assert_eq!(
+        usage::report(&entries, None),
+        [row("save-memory", 3, 1, 4.0), row("count", 2, 0, 2.0)]
+    );
+    assert_eq!(
+        usage::report(&entries, Some("2026-01-03T00:00:00Z")),
+        [row("save-memory", 2, 1, 5.0), row("count", 1, 0, 3.0)]
+    );
+}
+
+#[test]
+fn cli_appends_only_when_enabled() {
+    let dir = TestDir::new();
+    let log = dir.data_dir().join("usage.jsonl");
+
+    assert!(dir.command().args(["save", "one"]).status().unwrap().success());
+    assert!(!log.exists());
+
+    dir.write_config(json!({ "usage_log": true }));
+    assert!(dir.command().args(["save", "two"]).status().unwrap().success());
+    assert!(dir.command().args(["memory", "ls"]).status().unwrap().success());
+    assert!(!dir.command().args(["count", "--since", "bogus"]).status().unwrap().success());
+
+    let lines: Vec<serde_json::Value> = std::fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    let commands: Vec<(&str, bool)> =
+        lines.iter().map(|l| (l["command"].as_str().unwrap(), l["ok"] == true)).collect();
+    assert_eq!(commands, [("save-memory", true), ("memory list", true), ("count", false)]);
+    assert!(lines.iter().all(|l| l["ms"].is_number() && l["at"].is_string()));
+
+    let out = dir.command().args(["usage", "report"]).output().unwrap();
+    let report = String::from_utf8(out.stdout).unwrap();
+    assert!(report.starts_with("command"), "{}", report);
+    let count: Vec<&str> = report
+        .lines()
+        .find(|l| l.starts_with("count"))
+        .unwrap()
+        .split_whitespace()
+        .collect();
+    assert_eq!(count[..3], ["count", "1", "100%"], "{}", report);
+}